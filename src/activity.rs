@@ -0,0 +1,378 @@
+use crate::colors;
+use crate::{move_by, GameObject, MAP_HEIGHT, MAP_WIDTH, PLAYER};
+use crate::dijkstra::DijkstraMap;
+use crate::game::{Game, Map, PlayerAction};
+use crate::pathfinding::find_path;
+use crate::tile::Tile;
+
+// Travelling can go right across the level, unlike a monster chasing the player.
+const MAX_TRAVEL_LENGTH: i32 = MAP_WIDTH * MAP_HEIGHT;
+
+// Something the player keeps doing over several turns without a key press for each,
+// until it's done or something happens that they should know about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Activity {
+    Exploring,
+    Travelling { destination: (i32, i32) },
+}
+
+// What the player knew at the start of a turn, to tell what's new at the end of it.
+pub struct Watch {
+    hp: i32,
+    monsters_in_view: Vec<usize>,
+    // objects the player hasn't found yet, because they're on tiles that haven't been explored
+    undiscovered: Vec<usize>,
+}
+
+impl Watch {
+    pub fn new(game: &Game, game_objects: &[GameObject]) -> Self {
+        let undiscovered = (0..game_objects.len())
+            .filter(|&index| {
+                let (x, y) = game_objects[index].get_position();
+                !game.map[x as usize][y as usize].is_explored
+            })
+            .collect();
+        Watch {
+            hp: player_hp(game_objects),
+            monsters_in_view: monsters_in_view(game, game_objects),
+            undiscovered,
+        }
+    }
+
+    // Why the player should stop what they're doing, if anything happened since the watch started.
+    fn reason_to_stop(&self, game: &Game, game_objects: &[GameObject]) -> Option<String> {
+        if player_hp(game_objects) < self.hp {
+            return Some("You are hurt, and stop.".into());
+        }
+        let new_monster = monsters_in_view(game, game_objects)
+            .into_iter()
+            .find(|index| !self.monsters_in_view.contains(index));
+        if let Some(index) = new_monster {
+            return Some(format!("You see the {}.", game_objects[index].name));
+        }
+        self.undiscovered
+            .iter()
+            .find(|&&index| game.fov.is_in_fov(game_objects[index].x, game_objects[index].y))
+            .map(|&index| format!("You notice the {}.", game_objects[index].name))
+    }
+}
+
+fn player_hp(game_objects: &[GameObject]) -> i32 {
+    game_objects[PLAYER].fighter.map_or(0, |fighter| fighter.hp)
+}
+
+fn monsters_in_view(game: &Game, game_objects: &[GameObject]) -> Vec<usize> {
+    (0..game_objects.len())
+        .filter(|&index| {
+            game_objects[index].ai.is_some() && game.fov.is_in_fov(game_objects[index].x, game_objects[index].y)
+        })
+        .collect()
+}
+
+// Start an activity, unless there's a monster around to deal with first.
+pub fn start_activity(activity: Activity, game: &mut Game, game_objects: &[GameObject]) {
+    if let Some(&index) = monsters_in_view(game, game_objects).first() {
+        game.messages.add(
+            format!("Not with the {} around!", game_objects[index].name),
+            colors::WHITE,
+        );
+        return;
+    }
+    game.activity = Some(activity);
+}
+
+// Travel to a tile the player has already seen.
+pub fn travel_to(destination: (i32, i32), game: &mut Game, game_objects: &[GameObject]) {
+    let (x, y) = destination;
+    if !game.map[x as usize][y as usize].is_explored {
+        game.messages.add("You haven't been there yet.", colors::WHITE);
+        return;
+    }
+    start_activity(Activity::Travelling { destination }, game, game_objects);
+}
+
+// Travel back to where the last monster died.
+pub fn travel_to_last_corpse(game: &mut Game, game_objects: &[GameObject]) {
+    match game.last_corpse {
+        Some(position) => travel_to(position, game, game_objects),
+        None => game.messages.add("Nothing has died here yet.", colors::WHITE),
+    }
+}
+
+pub fn stop_activity(game: &mut Game) {
+    game.activity = None;
+}
+
+// Take the player's next step in whatever they're doing, instead of reading a key.
+pub fn continue_activity(game: &mut Game, game_objects: &mut [GameObject]) -> PlayerAction {
+    match game.activity {
+        Some(Activity::Exploring) => explore_step(game, game_objects),
+        Some(Activity::Travelling { destination }) => travel_step(destination, game, game_objects),
+        None => PlayerAction::DidntTakeTurn,
+    }
+}
+
+// Once everyone has moved, stop if something happened that the player should see to themselves.
+pub fn check_activity(watch: &Watch, game: &mut Game, game_objects: &[GameObject]) {
+    if game.activity.is_none() {
+        return;
+    }
+    if let Some(reason) = watch.reason_to_stop(game, game_objects) {
+        game.messages.add(reason, colors::LIGHT_GREY);
+        stop_activity(game);
+    }
+}
+
+// Walk one step toward the nearest tile the player hasn't seen yet. The way there only goes by what the
+// player knows: anything they haven't seen might be floor, so it's worth going to look.
+fn explore_step(game: &mut Game, game_objects: &mut [GameObject]) -> PlayerAction {
+    let unexplored: Vec<(i32, i32, i32)> = (0..MAP_WIDTH)
+        .flat_map(|x| (0..MAP_HEIGHT).map(move |y| (x, y)))
+        .filter(|&(x, y)| !game.map[x as usize][y as usize].is_explored)
+        .map(|(x, y)| (x, y, 0))
+        .collect();
+    let known_map = known_map(&game.map, Tile::empty());
+    let mut explore_map = DijkstraMap::new(MAP_WIDTH, MAP_HEIGHT);
+    explore_map.build(&known_map, &unexplored);
+
+    let (x, y) = game_objects[PLAYER].get_position();
+    match explore_map.lowest_neighbour(&known_map, x, y) {
+        Some(next) => step_to(next, game, game_objects),
+        None => {
+            game.messages.add("There is nothing left to explore here.", colors::LIGHT_GREY);
+            stop_activity(game);
+            PlayerAction::DidntTakeTurn
+        }
+    }
+}
+
+// Walk one step along the way to the destination. The way is worked out again every turn,
+// so it goes around monsters that move into it, but it only uses tiles the player has seen.
+fn travel_step(destination: (i32, i32), game: &mut Game, game_objects: &mut [GameObject]) -> PlayerAction {
+    let position = game_objects[PLAYER].get_position();
+    if position == destination {
+        stop_activity(game);
+        return PlayerAction::DidntTakeTurn;
+    }
+    match find_path(&known_map(&game.map, Tile::wall()), game_objects, position, destination, MAX_TRAVEL_LENGTH) {
+        Some(path) => step_to(path[0], game, game_objects),
+        None => {
+            game.messages.add("You don't know a way there.", colors::LIGHT_GREY);
+            stop_activity(game);
+            PlayerAction::DidntTakeTurn
+        }
+    }
+}
+
+// The map as far as the player knows it, with anything they haven't seen taken to be the given tile.
+fn known_map(map: &Map, unexplored: Tile) -> Map {
+    map.iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|&tile| if tile.is_explored { tile } else { unexplored })
+                .collect()
+        })
+        .collect()
+}
+
+// Move the player onto a neighbouring tile, or stop if something is standing there.
+fn step_to(next: (i32, i32), game: &mut Game, game_objects: &mut [GameObject]) -> PlayerAction {
+    let (x, y) = game_objects[PLAYER].get_position();
+    move_by(PLAYER, next.0 - x, next.1 - y, &game.map, game_objects);
+    if game_objects[PLAYER].get_position() == (x, y) {
+        game.messages.add("Something is in the way.", colors::LIGHT_GREY);
+        stop_activity(game);
+        return PlayerAction::DidntTakeTurn;
+    }
+    PlayerAction::TookTurn
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{create_game, end_player_turn};
+    use crate::monsters::builtin_monsters;
+    use crate::tile::map_from;
+    use super::*;
+
+    // A small level padded out to the full map size with walls, with just the player in it.
+    fn game_on(rows: &[&str], player: (i32, i32)) -> (Game, Vec<GameObject>) {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        let mut map = map_from(&[&"#".repeat(MAP_WIDTH as usize)[..]; MAP_HEIGHT as usize]);
+        let small = map_from(rows);
+        for (x, column) in small.into_iter().enumerate() {
+            for (y, tile) in column.into_iter().enumerate() {
+                map[x][y] = tile;
+            }
+        }
+        game.map = map;
+        game_objects.truncate(1);
+        game_objects[PLAYER].set_position(player.0, player.1);
+        game.update_fov(&game_objects);
+        (game, game_objects)
+    }
+
+    // Run an activity the way the game loop does, for at most the given number of turns.
+    fn run(game: &mut Game, game_objects: &mut [GameObject], turns: usize) -> usize {
+        let mut taken = 0;
+        while game.activity.is_some() && taken < turns {
+            let watch = Watch::new(game, game_objects);
+            if continue_activity(game, game_objects) == PlayerAction::TookTurn {
+                end_player_turn(game, game_objects);
+                check_activity(&watch, game, game_objects);
+                taken += 1;
+            }
+        }
+        taken
+    }
+
+    fn explore(game: &mut Game, game_objects: &mut [GameObject], turns: usize) -> usize {
+        start_activity(Activity::Exploring, game, game_objects);
+        run(game, game_objects, turns)
+    }
+
+    fn last_message(game: &Game) -> String {
+        game.messages.iter().last().map(|(message, _)| message).unwrap_or_default()
+    }
+
+    const TWO_ROOMS: [&str; 12] = [
+        "##########################",
+        "#....#####################",
+        "#....#####################",
+        "#.........................",
+        "#....####################.",
+        "#########################.",
+        "#########################.",
+        "#######################...",
+        "#######################...",
+        "#######################...",
+        "##########################",
+        "##########################",
+    ];
+
+    #[test]
+    fn explores_everything_reachable_then_says_so() {
+        let (mut game, mut game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        let turns = explore(&mut game, &mut game_objects, 200);
+        assert!(turns > 20 && turns < 200, "took {} turns", turns);
+        assert!(game.activity.is_none());
+        assert_eq!(last_message(&game), "There is nothing left to explore here.");
+        for (x, y) in [(24, 3), (25, 9), (23, 8)] {
+            assert!(game.map[x][y].is_explored, "({}, {}) wasn't explored", x, y);
+        }
+    }
+
+    #[test]
+    fn explores_by_what_the_player_knows() {
+        let (mut game, mut game_objects) = game_on(&["##########", "#....#...#", "##########"], (1, 1));
+        // forget everything from x = 5 on: the wall there might as well be floor until it's been seen
+        for column in game.map.iter_mut().skip(5) {
+            for tile in column.iter_mut() {
+                tile.is_explored = false;
+            }
+        }
+        let turns = explore(&mut game, &mut game_objects, 200);
+        assert!(turns > 0, "it went to look");
+        assert!(game.map[5][1].is_explored);
+        assert!(!game.map[6][1].is_explored, "the room behind the wall is still a secret");
+        assert_eq!(last_message(&game), "There is nothing left to explore here.");
+    }
+
+    #[test]
+    fn stops_when_a_monster_comes_into_view() {
+        let (mut game, mut game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        game_objects.push(builtin_monsters()[0].spawn(24, 8));
+        explore(&mut game, &mut game_objects, 200);
+        assert!(game.activity.is_none());
+        assert_eq!(last_message(&game), "You see the Orc.");
+        assert!(game.fov.is_in_fov(24, 8));
+    }
+
+    #[test]
+    fn stops_when_an_item_comes_into_view() {
+        let (mut game, mut game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        game_objects.push(GameObject::new(24, 9, '!', "healing potion", colors::VIOLET, false));
+        explore(&mut game, &mut game_objects, 200);
+        assert_eq!(last_message(&game), "You notice the healing potion.");
+    }
+
+    #[test]
+    fn stops_when_hurt() {
+        let (mut game, mut game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        let watch = Watch::new(&game, &game_objects);
+        game.activity = Some(Activity::Exploring);
+        game_objects[PLAYER].take_damage(1, &mut game);
+        check_activity(&watch, &mut game, &game_objects);
+        assert!(game.activity.is_none());
+        assert_eq!(last_message(&game), "You are hurt, and stop.");
+    }
+
+    #[test]
+    fn wont_start_with_a_monster_in_view() {
+        let (mut game, mut game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        game_objects.push(builtin_monsters()[0].spawn(3, 3));
+        game.update_fov(&game_objects);
+        assert_eq!(explore(&mut game, &mut game_objects, 200), 0);
+        assert_eq!(last_message(&game), "Not with the Orc around!");
+    }
+
+    #[test]
+    fn travels_to_an_explored_tile() {
+        let (mut game, mut game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        explore(&mut game, &mut game_objects, 200);
+        travel_to((1, 1), &mut game, &game_objects);
+        run(&mut game, &mut game_objects, 200);
+        assert_eq!(game_objects[PLAYER].get_position(), (1, 1));
+        assert!(game.activity.is_none());
+    }
+
+    #[test]
+    fn wont_travel_to_unexplored_tiles() {
+        let (mut game, game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        travel_to((24, 8), &mut game, &game_objects);
+        assert!(game.activity.is_none());
+        assert_eq!(last_message(&game), "You haven't been there yet.");
+    }
+
+    #[test]
+    fn never_travels_through_unexplored_tiles() {
+        let (mut game, mut game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        // the far room has been seen, but not the corridor leading to it
+        for x in 23..26 {
+            for y in 7..10 {
+                game.map[x][y].is_explored = true;
+            }
+        }
+        travel_to((24, 8), &mut game, &game_objects);
+        assert_eq!(run(&mut game, &mut game_objects, 200), 0);
+        assert_eq!(last_message(&game), "You don't know a way there.");
+        assert_eq!(game_objects[PLAYER].get_position(), (2, 2));
+    }
+
+    #[test]
+    fn travels_back_to_the_last_corpse() {
+        let (mut game, mut game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        explore(&mut game, &mut game_objects, 200);
+        let (x, y) = game_objects[PLAYER].get_position();
+        let mut orc = builtin_monsters()[0].spawn(3, 3);
+        orc.take_damage(100, &mut game);
+        game_objects.push(orc);
+        assert_eq!(game.last_corpse, Some((3, 3)));
+
+        travel_to_last_corpse(&mut game, &game_objects);
+        run(&mut game, &mut game_objects, 200);
+        assert_ne!((x, y), (3, 3));
+        assert_eq!(game_objects[PLAYER].get_position(), (3, 3));
+    }
+
+    #[test]
+    fn travel_stops_when_a_monster_comes_into_view() {
+        let (mut game, mut game_objects) = game_on(&TWO_ROOMS, (2, 2));
+        explore(&mut game, &mut game_objects, 200);
+        game_objects.push(builtin_monsters()[1].spawn(4, 1));
+        travel_to((1, 1), &mut game, &game_objects);
+        run(&mut game, &mut game_objects, 200);
+        assert!(game.activity.is_none());
+        assert_eq!(last_message(&game), "You see the Troll.");
+    }
+}
@@ -0,0 +1,296 @@
+// The arena: fights sides of monsters (and the player) against each other, thousands of times over,
+// with the same attacks as in the game, and prints how they did. It doesn't open a window,
+// so it can run anywhere, CI included; built with --no-default-features it doesn't even need tcod.
+//
+//   cargo run --bin arena -- player vs 2xOrc
+//   cargo run --bin arena --no-default-features -- player vs 2xOrc
+//   cargo run --bin arena -- --fights 5000 player:power=6,defense=3 vs Troll "Giant spider"
+
+use std::process;
+use roguelike::combat::{fresh_copy, simulate, Matchup};
+use roguelike::game::create_game;
+use roguelike::game_object::GameObject;
+use roguelike::monsters::{load_monsters, MonsterTemplate};
+use roguelike::PLAYER;
+
+const DEFAULT_FIGHTS: u32 = 1000;
+// The same seed every time unless asked otherwise, so the numbers only change when the game does.
+const DEFAULT_SEED: u64 = 1;
+
+const USAGE: &str = "\
+Usage: arena [--fights <number>] [--seed <number>] [<fighters> vs <fighters>]
+
+Each fighter is [<count>x]<name>[:<stat>=<value>,...], where the name is \"player\" or a monster's.
+The stats are hp, defense, power, speed, accuracy and evasion.
+Without any fighters, the player fights each monster in turn.";
+
+// One entry on the command line: how many of which fighter, and what to change about it.
+#[derive(Debug, PartialEq)]
+struct FighterSpec {
+    count: u32,
+    name: String,
+    overrides: Vec<(String, i32)>,
+}
+
+// Settings from the command line.
+#[derive(Debug, PartialEq)]
+struct Options {
+    fights: u32,
+    seed: u64,
+    // the two sides, or None for the player against each monster
+    sides: Option<[Vec<FighterSpec>; 2]>,
+}
+
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options { fights: DEFAULT_FIGHTS, seed: DEFAULT_SEED, sides: None };
+    let mut sides = vec![vec![]];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fights" => {
+                let value = args.next().ok_or("--fights needs a number after it")?;
+                options.fights = match value.parse() {
+                    Ok(fights) if fights > 0 => fights,
+                    _ => return Err(format!("\"{}\" is not a valid number of fights", value)),
+                };
+            }
+            "--seed" => {
+                let value = args.next().ok_or("--seed needs a number after it")?;
+                options.seed = value.parse().map_err(|_| format!("\"{}\" is not a valid seed", value))?;
+            }
+            "vs" => sides.push(vec![]),
+            _ => sides.last_mut().unwrap().push(parse_fighter(&arg)?),
+        }
+    }
+
+    match sides.len() {
+        1 if sides[0].is_empty() => {}
+        2 if sides.iter().all(|side| !side.is_empty()) => {
+            let second = sides.pop().unwrap();
+            let first = sides.pop().unwrap();
+            options.sides = Some([first, second]);
+        }
+        _ => return Err("Give the fighters on each side, with \"vs\" between the two sides".into()),
+    }
+    Ok(options)
+}
+
+// "3xOrc:power=4,hp=12" is three orcs with 4 power and 12 hit points each.
+fn parse_fighter(arg: &str) -> Result<FighterSpec, String> {
+    let (fighter, overrides) = match arg.split_once(':') {
+        Some((fighter, overrides)) => (fighter, Some(overrides)),
+        None => (arg, None),
+    };
+    let (count, name) = match fighter.split_once('x') {
+        Some((count, name)) if !count.is_empty() && count.chars().all(|c| c.is_ascii_digit()) => {
+            match count.parse() {
+                Ok(count) if count > 0 => (count, name),
+                _ => return Err(format!("\"{}\" is not a valid number of fighters", count)),
+            }
+        }
+        _ => (1, fighter),
+    };
+    if name.is_empty() {
+        return Err(format!("\"{}\" has no fighter name", arg));
+    }
+
+    let mut spec = FighterSpec { count, name: name.into(), overrides: vec![] };
+    for value in overrides.into_iter().flat_map(|overrides| overrides.split(',')) {
+        let (stat, amount) = value
+            .split_once('=')
+            .ok_or_else(|| format!("\"{}\" should look like <stat>=<value>", value))?;
+        let amount = amount
+            .parse()
+            .map_err(|_| format!("\"{}\" is not a valid value for {}", amount, stat))?;
+        spec.overrides.push((stat.to_string(), amount));
+    }
+    Ok(spec)
+}
+
+// Make the fighters for one spec, from the player or the monster with that name.
+fn make_fighters(
+    spec: &FighterSpec,
+    player: &GameObject,
+    monster_templates: &[MonsterTemplate],
+) -> Result<Vec<GameObject>, String> {
+    let mut fighters = vec![];
+    for _ in 0..spec.count {
+        let mut fighter = if spec.name.eq_ignore_ascii_case("player") {
+            fresh_copy(player)
+        } else {
+            monster_templates
+                .iter()
+                .find(|template| template.name.eq_ignore_ascii_case(&spec.name))
+                .ok_or_else(|| format!("There is no monster called \"{}\"", spec.name))?
+                .spawn(0, 0)
+        };
+        let stats = fighter.fighter.as_mut().unwrap();
+        for (stat, value) in &spec.overrides {
+            match stat.as_str() {
+                "hp" if *value > 0 => {
+                    stats.base_max_hp = *value;
+                    stats.hp = *value;
+                }
+                "defense" => stats.base_defense = *value,
+                "power" => stats.base_power = *value,
+                "speed" if *value > 0 => stats.base_speed = *value,
+                "accuracy" => stats.accuracy = *value,
+                "evasion" => stats.evasion = *value,
+                "hp" | "speed" => return Err(format!("{} must be positive, got {}", stat, value)),
+                _ => return Err(format!("Unknown stat \"{}\"", stat)),
+            }
+        }
+        fighters.push(fighter);
+    }
+    Ok(fighters)
+}
+
+fn describe(specs: &[FighterSpec]) -> String {
+    let names: Vec<String> = specs
+        .iter()
+        .map(|spec| {
+            let mut name = if spec.count > 1 { format!("{}x{}", spec.count, spec.name) } else { spec.name.clone() };
+            if !spec.overrides.is_empty() {
+                let overrides: Vec<String> =
+                    spec.overrides.iter().map(|(stat, value)| format!("{}={}", stat, value)).collect();
+                name = format!("{}:{}", name, overrides.join(","));
+            }
+            name
+        })
+        .collect();
+    names.join(" ")
+}
+
+// The results are from the first side's point of view: its wins, its losses and the hit points it has left.
+fn print_row(name: &str, matchup: &Matchup) {
+    println!(
+        "{:<40} {:>7.1} {:>7.1} {:>7.1} {:>7.1} {:>8.1} {:>8.1}",
+        name,
+        matchup.win_rate(0),
+        matchup.win_rate(1),
+        matchup.draw_rate(),
+        matchup.average_turns(),
+        matchup.average_hp_left(0),
+        matchup.average_hp_left(1),
+    );
+}
+
+fn make_side(
+    specs: &[FighterSpec],
+    player: &GameObject,
+    monster_templates: &[MonsterTemplate],
+) -> Result<Vec<GameObject>, String> {
+    let mut side = vec![];
+    for spec in specs {
+        side.extend(make_fighters(spec, player, monster_templates)?);
+    }
+    Ok(side)
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let monster_templates =
+        load_monsters().map_err(|error| format!("Could not load the monster definitions: {}", error))?;
+    let (_, game_objects) = create_game(&monster_templates, options.seed);
+    let player = &game_objects[PLAYER];
+
+    // the player against each monster, unless the sides were given
+    let matchups = match options.sides {
+        Some(sides) => vec![sides],
+        None => monster_templates
+            .iter()
+            .map(|template| {
+                let spec = |name: &str| FighterSpec { count: 1, name: name.into(), overrides: vec![] };
+                [vec![spec("player")], vec![spec(&template.name)]]
+            })
+            .collect(),
+    };
+
+    println!("{} fights per matchup, seed {}", options.fights, options.seed);
+    println!(
+        "{:<40} {:>7} {:>7} {:>7} {:>7} {:>8} {:>8}",
+        "matchup", "win %", "loss %", "draw %", "turns", "hp left", "foe hp"
+    );
+    for specs in &matchups {
+        let sides = [
+            make_side(&specs[0], player, &monster_templates)?,
+            make_side(&specs[1], player, &monster_templates)?,
+        ];
+        let name = format!("{} vs {}", describe(&specs[0]), describe(&specs[1]));
+        print_row(&name, &simulate(&sides, options.fights, options.seed));
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    if let Err(error) = run(options) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use roguelike::monsters::builtin_monsters;
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_options(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_fighters_means_the_player_against_each_monster() {
+        assert_eq!(parse(&[]), Ok(Options { fights: DEFAULT_FIGHTS, seed: DEFAULT_SEED, sides: None }));
+    }
+
+    #[test]
+    fn fighters_have_counts_and_stat_overrides() {
+        let options = parse(&["--fights", "50", "player:power=7,hp=40", "vs", "3xOrc", "Giant spider"]).unwrap();
+        assert_eq!(options.fights, 50);
+        let sides = options.sides.unwrap();
+        assert_eq!(
+            sides[0],
+            vec![FighterSpec {
+                count: 1,
+                name: "player".into(),
+                overrides: vec![("power".into(), 7), ("hp".into(), 40)],
+            }]
+        );
+        assert_eq!(sides[1][0], FighterSpec { count: 3, name: "Orc".into(), overrides: vec![] });
+        assert_eq!(sides[1][1].name, "Giant spider");
+        assert_eq!(describe(&sides[0]), "player:power=7,hp=40");
+        assert_eq!(describe(&sides[1]), "3xOrc Giant spider");
+    }
+
+    #[test]
+    fn bad_arguments_are_reported() {
+        assert!(parse(&["Orc"]).is_err(), "only one side");
+        assert!(parse(&["Orc", "vs"]).is_err(), "nobody on the second side");
+        assert!(parse(&["Orc", "vs", "Troll", "vs", "Bat"]).is_err(), "three sides");
+        assert!(parse(&["--fights", "0"]).is_err());
+        assert!(parse(&["0xOrc", "vs", "Troll"]).is_err());
+        assert!(parse(&["Orc:power", "vs", "Troll"]).is_err());
+        assert!(parse(&["Orc:power=lots", "vs", "Troll"]).is_err());
+    }
+
+    #[test]
+    fn overrides_change_the_fighters() {
+        let (_, game_objects) = create_game(&builtin_monsters(), 1);
+        let spec = parse_fighter("2xtroll:hp=5,accuracy=3").unwrap();
+        let trolls = make_fighters(&spec, &game_objects[PLAYER], &builtin_monsters()).unwrap();
+        assert_eq!(trolls.len(), 2);
+        let troll = trolls[0].fighter.unwrap();
+        assert_eq!((troll.hp, troll.base_max_hp, troll.accuracy), (5, 5, 3));
+
+        let unknown = |arg: &str| make_fighters(&parse_fighter(arg).unwrap(), &game_objects[PLAYER], &builtin_monsters());
+        assert!(unknown("Dragon").is_err());
+        assert!(unknown("Orc:luck=3").is_err());
+        assert!(unknown("Orc:speed=0").is_err());
+    }
+}
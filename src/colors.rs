@@ -0,0 +1,42 @@
+// The colors everything is drawn in. With the window (the "tui" feature) these are tcod's own.
+// Without it, like in the arena, nothing gets drawn, so they're plain numbers with the same names
+// and values, and they load from the monsters file and save files just the same.
+#[cfg(feature = "tui")]
+pub use tcod::colors::*;
+
+#[cfg(not(feature = "tui"))]
+pub use self::plain::*;
+
+#[cfg(not(feature = "tui"))]
+mod plain {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Color {
+        pub r: u8,
+        pub g: u8,
+        pub b: u8,
+    }
+
+    // only the ones the game uses outside of the window code
+    pub const WHITE: Color = Color { r: 255, g: 255, b: 255 };
+    pub const RED: Color = Color { r: 255, g: 0, b: 0 };
+    pub const DARK_RED: Color = Color { r: 191, g: 0, b: 0 };
+    pub const ORANGE: Color = Color { r: 255, g: 127, b: 0 };
+    pub const DARKER_ORANGE: Color = Color { r: 127, g: 63, b: 0 };
+    pub const AMBER: Color = Color { r: 255, g: 191, b: 0 };
+    pub const YELLOW: Color = Color { r: 255, g: 255, b: 0 };
+    pub const LIGHT_YELLOW: Color = Color { r: 255, g: 255, b: 115 };
+    pub const GREEN: Color = Color { r: 0, g: 255, b: 0 };
+    pub const LIGHT_GREEN: Color = Color { r: 115, g: 255, b: 115 };
+    pub const DARKER_GREEN: Color = Color { r: 0, g: 127, b: 0 };
+    pub const DESATURATED_GREEN: Color = Color { r: 63, g: 127, b: 63 };
+    pub const SKY: Color = Color { r: 0, g: 191, b: 255 };
+    pub const LIGHT_BLUE: Color = Color { r: 115, g: 115, b: 255 };
+    pub const VIOLET: Color = Color { r: 127, g: 0, b: 255 };
+    pub const LIGHT_VIOLET: Color = Color { r: 185, g: 115, b: 255 };
+    pub const SEPIA: Color = Color { r: 127, g: 101, b: 63 };
+    pub const DARK_SEPIA: Color = Color { r: 94, g: 75, b: 47 };
+    pub const LIGHT_GREY: Color = Color { r: 159, g: 159, b: 159 };
+    pub const DARK_GREY: Color = Color { r: 95, g: 95, b: 95 };
+}
@@ -0,0 +1,321 @@
+use rand::Rng;
+use crate::effects::start_turn;
+use crate::fov::Fov;
+use crate::game::{mut_two, Game};
+use crate::game_object::GameObject;
+use crate::messages::Messages;
+use crate::rng::GameRng;
+use crate::scheduler::Scheduler;
+
+// Chance to hit, in percent, when accuracy and evasion are even; each point of difference moves it this much.
+const BASE_HIT_CHANCE: i32 = 75;
+const HIT_CHANCE_PER_POINT: i32 = 5;
+// Nothing is ever certain to hit, or to miss.
+const MIN_HIT_CHANCE: i32 = 5;
+const MAX_HIT_CHANCE: i32 = 95;
+// A critical hit does this many times the damage, before armour.
+const CRITICAL_CHANCE: i32 = 5;
+const CRITICAL_MULTIPLIER: i32 = 2;
+// Even a blow the armour should stop gets this much through.
+const CHIP_DAMAGE: i32 = 1;
+// A fight where nobody has won after this many turns is a draw.
+const MAX_FIGHT_TURNS: u32 = 10_000;
+
+// Everything rolled for one attack, so it can be shown in the log.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AttackRoll {
+    // a d100 roll (0 to 99), which hits if it's under the hit chance
+    pub to_hit: i32,
+    pub hit_chance: i32,
+    pub critical: bool,
+    // damage before armour, and what's left after it; both 0 for a miss
+    pub rolled_damage: i32,
+    pub damage: i32,
+}
+
+impl AttackRoll {
+    pub fn is_hit(&self) -> bool {
+        self.to_hit < self.hit_chance
+    }
+}
+
+pub fn hit_chance(accuracy: i32, evasion: i32) -> i32 {
+    (BASE_HIT_CHANCE + (accuracy - evasion) * HIT_CHANCE_PER_POINT).clamp(MIN_HIT_CHANCE, MAX_HIT_CHANCE)
+}
+
+// Roll to hit, then for damage: anywhere from half the power to one and a half times it,
+// doubled on a critical hit, less the defender's armour but never below the chip damage.
+pub fn roll_attack<R: Rng>(power: i32, accuracy: i32, defense: i32, evasion: i32, rng: &mut R) -> AttackRoll {
+    let hit_chance = hit_chance(accuracy, evasion);
+    let to_hit = rng.gen_range(0, 100);
+    if to_hit >= hit_chance {
+        return AttackRoll { to_hit, hit_chance, critical: false, rolled_damage: 0, damage: 0 };
+    }
+
+    let critical = rng.gen_range(0, 100) < CRITICAL_CHANCE;
+    let power = power.max(0);
+    let mut rolled_damage = rng.gen_range(power / 2, power + power / 2 + 1);
+    if critical {
+        rolled_damage *= CRITICAL_MULTIPLIER;
+    }
+    let damage = (rolled_damage - defense).max(CHIP_DAMAGE);
+    AttackRoll { to_hit, hit_chance, critical, rolled_damage, damage }
+}
+
+// How one arena fight ended. Both sides are numbered 0 and 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FightResult {
+    // None for a draw
+    winner: Option<usize>,
+    // every action by any fighter counts as a turn
+    turns: u32,
+    // each side's hit points left between its fighters
+    hp_left: [i32; 2],
+}
+
+// How two sides did against each other over many fights, added up.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Matchup {
+    pub fights: u32,
+    pub wins: [u32; 2],
+    // added up over every fight, so it can get big
+    pub turns: u64,
+    pub hp_left: [i32; 2],
+}
+
+impl Matchup {
+    pub fn draws(&self) -> u32 {
+        self.fights - self.wins[0] - self.wins[1]
+    }
+
+    // in percent
+    pub fn win_rate(&self, side: usize) -> f32 {
+        100.0 * self.wins[side] as f32 / self.fights.max(1) as f32
+    }
+
+    pub fn draw_rate(&self) -> f32 {
+        100.0 * self.draws() as f32 / self.fights.max(1) as f32
+    }
+
+    pub fn average_turns(&self) -> f32 {
+        self.turns as f32 / self.fights.max(1) as f32
+    }
+
+    // over all the fights, won or lost
+    pub fn average_hp_left(&self, side: usize) -> f32 {
+        self.hp_left[side] as f32 / self.fights.max(1) as f32
+    }
+}
+
+// Two sides take turns at each other, by speed and with the same attacks as in the dungeon,
+// until one of them is wiped out. Everybody attacks the first fighter still standing on the other side.
+// The fighters are copied, so the same sides can fight again.
+fn fight(sides: &[Vec<GameObject>; 2], game: &mut Game) -> FightResult {
+    let scheduler = Scheduler::default();
+    let mut fighters: Vec<GameObject> = sides.iter().flatten().map(fresh_copy).collect();
+    let side_of = |index: usize| if index < sides[0].len() { 0 } else { 1 };
+    let standing = |fighters: &[GameObject], side: usize| {
+        (0..fighters.len()).find(|&index| side_of(index) == side && fighters[index].alive)
+    };
+
+    let mut turns = 0;
+    let mut winner = None;
+    while turns < MAX_FIGHT_TURNS {
+        let attacker = match scheduler.next_actor(&mut fighters) {
+            Some(attacker) => attacker,
+            None => break,
+        };
+        if !fighters[attacker].alive {
+            // a dead player keeps its stats, but it's out of the fight
+            scheduler.spend(&mut fighters[attacker]);
+            continue;
+        }
+        turns += 1;
+        if start_turn(attacker, game, &mut fighters) {
+            if let Some(defender) = standing(&fighters, 1 - side_of(attacker)) {
+                let (attacker, defender) = mut_two(attacker, defender, &mut fighters);
+                attacker.attack(defender, game);
+            }
+        }
+        scheduler.spend(&mut fighters[attacker]);
+
+        if let Some(side) = (0..2).find(|&side| standing(&fighters, side).is_none()) {
+            winner = Some(1 - side);
+            break;
+        }
+    }
+
+    let mut hp_left = [0; 2];
+    for (index, fighter) in fighters.iter().enumerate() {
+        hp_left[side_of(index)] += fighter.fighter.map_or(0, |fighter| fighter.hp.max(0));
+    }
+    FightResult { winner, turns, hp_left }
+}
+
+// Fight the same two sides again and again. The same seed always gives the same results.
+pub fn simulate(sides: &[Vec<GameObject>; 2], fights: u32, seed: u64) -> Matchup {
+    let mut matchup = Matchup::default();
+    let mut rng = GameRng::new(seed);
+    for _ in 0..fights {
+        let mut game = arena_game(rng);
+        let result = fight(sides, &mut game);
+        rng = game.rng;
+        matchup.fights += 1;
+        if let Some(winner) = result.winner {
+            matchup.wins[winner] += 1;
+        }
+        matchup.turns += result.turns as u64;
+        matchup.hp_left[0] += result.hp_left[0];
+        matchup.hp_left[1] += result.hp_left[1];
+    }
+    matchup
+}
+
+// Somewhere for a fight to happen that isn't part of any real game: no map, no inventory,
+// and the log and corpses go nowhere.
+fn arena_game(rng: GameRng) -> Game {
+    Game {
+        map: vec![],
+        messages: Messages::new(),
+        inventory: vec![],
+        dungeon_level: 1,
+        seed: 0,
+        last_corpse: None,
+        rng,
+        monster_templates: vec![],
+        fov: Fov::default(),
+        activity: None,
+        projectiles: vec![],
+        flee_maps: None,
+    }
+}
+
+// A fighter ready for the arena: just its looks and its stats, at full health and with no effects.
+pub fn fresh_copy(game_object: &GameObject) -> GameObject {
+    let mut copy = GameObject::new(0, 0, game_object.char, &game_object.name, game_object.color, true);
+    copy.alive = true;
+    copy.fighter = game_object.fighter.map(|mut fighter| {
+        fighter.hp = fighter.base_max_hp;
+        fighter
+    });
+    copy
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_game, PLAYER};
+    use crate::monsters::builtin_monsters;
+    use crate::rng::GameRng;
+    use super::*;
+
+    #[test]
+    fn hit_chance_follows_accuracy_and_evasion_within_limits() {
+        assert_eq!(hit_chance(0, 0), BASE_HIT_CHANCE);
+        assert_eq!(hit_chance(2, 0), BASE_HIT_CHANCE + 2 * HIT_CHANCE_PER_POINT);
+        assert_eq!(hit_chance(0, 3), BASE_HIT_CHANCE - 3 * HIT_CHANCE_PER_POINT);
+        assert_eq!(hit_chance(100, 0), MAX_HIT_CHANCE);
+        assert_eq!(hit_chance(0, 100), MIN_HIT_CHANCE);
+    }
+
+    #[test]
+    fn rolls_stay_in_range() {
+        let mut rng = GameRng::new(5);
+        let (mut hits, mut criticals) = (0, 0);
+        for _ in 0..10_000 {
+            let roll = roll_attack(6, 0, 2, 0, &mut rng);
+            if !roll.is_hit() {
+                assert_eq!(roll.damage, 0);
+                continue;
+            }
+            hits += 1;
+            if roll.critical {
+                criticals += 1;
+                assert!((6..=18).contains(&roll.rolled_damage), "{:?}", roll);
+            } else {
+                assert!((3..=9).contains(&roll.rolled_damage), "{:?}", roll);
+            }
+            assert_eq!(roll.damage, (roll.rolled_damage - 2).max(CHIP_DAMAGE));
+        }
+        // about 75% hits, and about 5% of those critical
+        assert!((7_200..7_800).contains(&hits), "{} hits", hits);
+        assert!((250..500).contains(&criticals), "{} criticals", criticals);
+    }
+
+    #[test]
+    fn armour_never_stops_every_blow() {
+        let mut rng = GameRng::new(6);
+        let damage: i32 = (0..100).map(|_| roll_attack(1, 0, 10, 0, &mut rng).damage).sum();
+        assert!(damage > 0);
+        assert!((0..100).all(|_| roll_attack(1, 0, 10, 0, &mut rng).damage <= CHIP_DAMAGE));
+    }
+
+    #[test]
+    fn a_fight_goes_on_until_one_side_is_down() {
+        let mut game = arena_game(GameRng::new(1));
+        let orc = || builtin_monsters()[0].spawn(0, 0);
+        let result = fight(&[vec![orc(), orc()], vec![orc()]], &mut game);
+        let winner = result.winner.unwrap();
+        assert!(result.hp_left[winner] > 0);
+        assert_eq!(result.hp_left[1 - winner], 0);
+        assert!(result.turns > 2);
+    }
+
+    #[test]
+    fn the_same_fighters_can_fight_again() {
+        let sides = [vec![builtin_monsters()[1].spawn(0, 0)], vec![builtin_monsters()[0].spawn(0, 0)]];
+        let matchup = simulate(&sides, 10, 1);
+        assert_eq!(matchup.fights, 10);
+        assert_eq!(sides[0][0].fighter.map(|fighter| fighter.hp), Some(16));
+        assert!(sides.iter().flatten().all(|fighter| fighter.alive));
+    }
+
+    #[test]
+    fn a_dead_player_is_out_of_the_fight() {
+        let (_, mut game_objects) = create_game(&builtin_monsters(), 1);
+        let mut player = game_objects.swap_remove(PLAYER);
+        player.fighter.as_mut().unwrap().base_max_hp = 1;
+        let mut troll = builtin_monsters()[1].spawn(0, 0);
+        let stats = troll.fighter.as_mut().unwrap();
+        (stats.base_max_hp, stats.base_power, stats.accuracy) = (1_000, 50, 100);
+
+        let mut game = arena_game(GameRng::new(1));
+        let result = fight(&[vec![player, builtin_monsters()[0].spawn(0, 0)], vec![troll]], &mut game);
+        assert_eq!(result.winner, Some(1));
+        // the player is left well below 0 hit points, but that doesn't count against their side
+        assert_eq!(result.hp_left[0], 0);
+        let texts: Vec<String> = game.messages.iter().map(|(text, _)| text).collect();
+        let died = texts.iter().position(|text| text == "You died!").unwrap();
+        assert!(texts[died..].iter().all(|text| !text.starts_with("Player attacks")));
+    }
+
+    #[test]
+    fn simulations_are_repeatable() {
+        let sides = [vec![builtin_monsters()[1].spawn(0, 0)], vec![builtin_monsters()[3].spawn(0, 0)]];
+        assert_eq!(simulate(&sides, 50, 7), simulate(&sides, 50, 7));
+    }
+
+    // Thousands of fights between a fresh player and each of the built-in monsters, reported as win rates.
+    // Run with --nocapture to see the table, or use the arena for other matchups.
+    #[test]
+    fn win_rates_per_matchup() {
+        let monster_templates = builtin_monsters();
+        let (_, mut game_objects) = create_game(&monster_templates, 1);
+        let player = game_objects.swap_remove(PLAYER);
+
+        println!("{:<15} {:>8} {:>8} {:>8}", "monster", "win %", "loss %", "hp left");
+        for template in &monster_templates {
+            let sides = [vec![fresh_copy(&player)], vec![template.spawn(0, 0)]];
+            let matchup = simulate(&sides, 2_000, 1);
+            println!(
+                "{:<15} {:>8.1} {:>8.1} {:>8.1}",
+                template.name,
+                matchup.win_rate(0),
+                matchup.win_rate(1),
+                matchup.average_hp_left(0),
+            );
+            // the rates themselves are for people tuning the monsters to look at, not for the test to judge
+            assert_eq!(matchup.fights, 2_000);
+            assert_eq!(matchup.draws(), 0, "no fight should drag on forever");
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use crate::game::Map;
+use crate::pathfinding::NEIGHBOURS;
+use crate::squeezes_between_walls;
+
+// How much more a fleeing monster cares about getting far away than about the way it goes.
+// Above 100 it'll run past the player to get to a better spot rather than into a corner.
+const FLEE_FACTOR_PERCENT: i32 = 120;
+
+// The cost of getting from every tile to the nearest of some goals, like the player or the items
+// on the floor. A monster walks towards the goals by stepping to its lowest neighbour.
+// The buffers are kept between builds, so it's cheap to rebuild every turn.
+pub struct DijkstraMap {
+    width: i32,
+    height: i32,
+    values: Vec<Option<i32>>,
+    open: BinaryHeap<Reverse<(i32, (i32, i32))>>,
+}
+
+impl DijkstraMap {
+    pub fn new(width: i32, height: i32) -> Self {
+        DijkstraMap {
+            width,
+            height,
+            values: vec![None; (width * height) as usize],
+            open: BinaryHeap::new(),
+        }
+    }
+
+    // Start over from the given sources: (x, y, value) where a lower value makes the source more attractive.
+    // Every step onto a floor tile costs 1.
+    pub fn build(&mut self, map: &Map, sources: &[(i32, i32, i32)]) {
+        self.build_weighted(map, sources, |_, _| 1);
+    }
+
+    // Add some sources without starting over, every step costing 1 like in build.
+    pub fn update(&mut self, map: &Map, sources: &[(i32, i32, i32)]) {
+        self.update_weighted(map, sources, |_, _| 1);
+    }
+
+    // Like build, but stepping onto a tile costs whatever step_cost says (at least 1),
+    // e.g. more for tiles a monster is standing on.
+    pub fn build_weighted<F: Fn(i32, i32) -> i32>(&mut self, map: &Map, sources: &[(i32, i32, i32)], step_cost: F) {
+        self.values.iter_mut().for_each(|value| *value = None);
+        self.update_weighted(map, sources, step_cost);
+    }
+
+    // Add some sources without starting over. This only ever lowers values,
+    // so it can't take away a source; build again for that.
+    pub fn update_weighted<F: Fn(i32, i32) -> i32>(&mut self, map: &Map, sources: &[(i32, i32, i32)], step_cost: F) {
+        for &(x, y, value) in sources {
+            if self.is_walkable(map, x, y) && self.get(x, y).is_none_or(|old| value < old) {
+                let index = self.index(x, y);
+                self.values[index] = Some(value);
+                self.open.push(Reverse((value, (x, y))));
+            }
+        }
+
+        // spread out from the cheapest tile first, so each tile is settled the first time it's taken out
+        while let Some(Reverse((value, (x, y)))) = self.open.pop() {
+            if self.get(x, y) != Some(value) {
+                // already reached more cheaply since this was queued
+                continue;
+            }
+            for (dx, dy) in NEIGHBOURS.iter() {
+                let (next_x, next_y) = (x + dx, y + dy);
+                if !self.is_walkable(map, next_x, next_y) || squeezes_between_walls(x, y, *dx, *dy, map) {
+                    continue;
+                }
+                let next_value = value + step_cost(next_x, next_y).max(1);
+                if self.get(next_x, next_y).is_none_or(|old| next_value < old) {
+                    let index = self.index(next_x, next_y);
+                    self.values[index] = Some(next_value);
+                    self.open.push(Reverse((next_value, (next_x, next_y))));
+                }
+            }
+        }
+    }
+
+    // The cost of getting from this tile to the nearest source, or None if no source can be reached.
+    pub fn get(&self, x: i32, y: i32) -> Option<i32> {
+        if self.in_map(x, y) {
+            self.values[self.index(x, y)]
+        } else {
+            None
+        }
+    }
+
+    // The neighbouring tile that's closest to a source, if it's any closer than this one.
+    pub fn lowest_neighbour(&self, map: &Map, x: i32, y: i32) -> Option<(i32, i32)> {
+        let mut lowest = self.get(x, y).map(|value| (value, (x, y)));
+        for (dx, dy) in NEIGHBOURS.iter() {
+            let (next_x, next_y) = (x + dx, y + dy);
+            if !self.in_map(next_x, next_y) || squeezes_between_walls(x, y, *dx, *dy, map) {
+                continue;
+            }
+            if let Some(value) = self.get(next_x, next_y) {
+                if lowest.is_none_or(|(lowest_value, _)| value < lowest_value) {
+                    lowest = Some((value, (next_x, next_y)));
+                }
+            }
+        }
+        lowest
+            .map(|(_, position)| position)
+            .filter(|&position| position != (x, y))
+    }
+
+    // Start over as a map for running away from the sources of another one: walking down it leads away
+    // from them, but towards open space instead of into the nearest dead end.
+    pub fn build_flee(&mut self, map: &Map, from: &DijkstraMap) {
+        let sources: Vec<(i32, i32, i32)> = (0..from.width)
+            .flat_map(|x| (0..from.height).map(move |y| (x, y)))
+            .filter_map(|(x, y)| {
+                from.get(x, y)
+                    .map(|value| (x, y, -value * FLEE_FACTOR_PERCENT / 100))
+            })
+            .collect();
+        self.build(map, &sources);
+    }
+
+    fn in_map(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
+    }
+
+    fn is_walkable(&self, map: &Map, x: i32, y: i32) -> bool {
+        self.in_map(x, y) && !map[x as usize][y as usize].is_blocked
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (x * self.height + y) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tile::{map_from, Tile};
+    use crate::{MAP_HEIGHT, MAP_WIDTH};
+    use super::*;
+
+    const ROOMS: [&str; 7] = [
+        "##########",
+        "#...#....#",
+        "#...#....#",
+        "#........#",
+        "#...#....#",
+        "#...#...##",
+        "##########",
+    ];
+
+    fn build(rows: &[&str], sources: &[(i32, i32, i32)]) -> (Map, DijkstraMap) {
+        let map = map_from(rows);
+        let mut dijkstra_map = DijkstraMap::new(map.len() as i32, map[0].len() as i32);
+        dijkstra_map.build(&map, sources);
+        (map, dijkstra_map)
+    }
+
+    #[test]
+    fn distances_from_one_source() {
+        let (_, dijkstra_map) = build(&ROOMS, &[(1, 1, 0)]);
+        assert_eq!(dijkstra_map.get(1, 1), Some(0));
+        assert_eq!(dijkstra_map.get(3, 3), Some(2));
+        // through the doorway at (4, 3)
+        assert_eq!(dijkstra_map.get(5, 1), Some(5));
+        assert_eq!(dijkstra_map.get(8, 5), None, "walls can't be reached");
+        assert_eq!(dijkstra_map.get(4, 2), None);
+        assert_eq!(dijkstra_map.get(-1, 3), None);
+    }
+
+    #[test]
+    fn each_tile_goes_to_its_nearest_source() {
+        let (_, dijkstra_map) = build(&ROOMS, &[(1, 1, 0), (8, 4, 0)]);
+        assert_eq!(dijkstra_map.get(2, 2), Some(1));
+        assert_eq!(dijkstra_map.get(7, 2), Some(2));
+    }
+
+    #[test]
+    fn lower_source_values_pull_harder() {
+        let (_, dijkstra_map) = build(&ROOMS, &[(1, 3, 0), (8, 3, -10)]);
+        // (3, 3) is two steps from the first source, and five from the second which starts 10 lower
+        assert_eq!(dijkstra_map.get(3, 3), Some(-5));
+    }
+
+    #[test]
+    fn closed_off_tiles_are_unreachable() {
+        let (map, dijkstra_map) = build(&["#######", "#..#..#", "#######"], &[(1, 1, 0)]);
+        assert_eq!(dijkstra_map.get(2, 1), Some(1));
+        assert_eq!(dijkstra_map.get(4, 1), None);
+        assert_eq!(dijkstra_map.lowest_neighbour(&map, 5, 1), None);
+    }
+
+    #[test]
+    fn weighted_tiles_are_walked_around() {
+        let map = map_from(&["#######", "#.....#", "#.....#", "#######"]);
+        let mut dijkstra_map = DijkstraMap::new(7, 4);
+        dijkstra_map.build_weighted(&map, &[(1, 1, 0)], |x, y| if (x, y) == (3, 1) || (x, y) == (3, 2) { 10 } else { 1 });
+        // the only ways past x = 3 are expensive
+        assert_eq!(dijkstra_map.get(2, 1), Some(1));
+        assert_eq!(dijkstra_map.get(3, 1), Some(11));
+        assert_eq!(dijkstra_map.get(4, 1), Some(12));
+    }
+
+    #[test]
+    fn update_matches_a_full_build() {
+        let map = map_from(&ROOMS);
+        let mut updated = DijkstraMap::new(10, 7);
+        updated.build(&map, &[(1, 1, 0)]);
+        updated.update(&map, &[(8, 4, 0)]);
+        let (_, built) = build(&ROOMS, &[(1, 1, 0), (8, 4, 0)]);
+        assert!(updated.values == built.values);
+    }
+
+    #[test]
+    fn lowest_neighbours_lead_to_the_source() {
+        let (map, dijkstra_map) = build(&ROOMS, &[(8, 1, 0)]);
+        let mut position = (1, 5);
+        for _ in 0..20 {
+            match dijkstra_map.lowest_neighbour(&map, position.0, position.1) {
+                Some(next) => position = next,
+                None => break,
+            }
+        }
+        assert_eq!(position, (8, 1));
+        assert_eq!(dijkstra_map.lowest_neighbour(&map, 8, 1), None);
+    }
+
+    #[test]
+    fn fleeing_leads_away_from_the_source() {
+        let (map, dijkstra_map) = build(&ROOMS, &[(8, 3, 0)]);
+        let mut flee_map = DijkstraMap::new(10, 7);
+        flee_map.build_flee(&map, &dijkstra_map);
+        let (x, y) = flee_map.lowest_neighbour(&map, 6, 3).unwrap();
+        assert!(dijkstra_map.get(x, y) > dijkstra_map.get(6, 3));
+    }
+
+    #[test]
+    fn rebuilding_on_a_full_size_map() {
+        let map = vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        let mut dijkstra_map = DijkstraMap::new(MAP_WIDTH, MAP_HEIGHT);
+        for turn in 0..50 {
+            dijkstra_map.build(&map, &[(turn, 20, 0)]);
+        }
+        assert_eq!(dijkstra_map.get(MAP_WIDTH - 1, 0), Some(MAP_WIDTH - 50));
+    }
+}
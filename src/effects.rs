@@ -0,0 +1,288 @@
+use std::mem;
+use serde::{Deserialize, Serialize};
+use crate::colors::*;
+use crate::{Game, GameObject, PLAYER};
+
+// How fast something hasted is, in percent of its usual speed.
+pub const HASTE_SPEED_PERCENT: i32 = 200;
+
+// Something that happens to a fighter every turn for a while.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Effect {
+    // loses this many hit points every turn
+    Poison(i32),
+    // gets this many hit points back every turn
+    Regeneration(i32),
+    // loses its turns
+    Stun,
+    // acts twice as often
+    Haste,
+}
+
+impl Effect {
+    // what the panel shows for the player's active effects
+    pub fn letter(self) -> char {
+        match self {
+            Effect::Poison(_) => 'P',
+            Effect::Regeneration(_) => 'R',
+            Effect::Stun => 'S',
+            Effect::Haste => 'H',
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Effect::Poison(_) => GREEN,
+            Effect::Regeneration(_) => LIGHT_VIOLET,
+            Effect::Stun => YELLOW,
+            Effect::Haste => LIGHT_BLUE,
+        }
+    }
+
+    fn adjective(self) -> &'static str {
+        match self {
+            Effect::Poison(_) => "poisoned",
+            Effect::Regeneration(_) => "regenerating",
+            Effect::Stun => "stunned",
+            Effect::Haste => "hasted",
+        }
+    }
+
+    // Poison(1) and Poison(3) are the same effect, just of different strength.
+    fn is_same_kind(self, other: Effect) -> bool {
+        mem::discriminant(&self) == mem::discriminant(&other)
+    }
+
+    pub fn validate(self) -> Result<(), String> {
+        match self {
+            Effect::Poison(amount) | Effect::Regeneration(amount) if amount <= 0 => {
+                Err(format!("{} must be positive, got {}", self.adjective(), amount))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// An effect and how many more of its owner's turns it lasts.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub effect: Effect,
+    pub turns: i32,
+    // whether the player caused it, so a kill by poison still earns them experience
+    pub from_player: bool,
+}
+
+// An effect a monster's attacks pass on to whatever they hit, some of the time.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OnHit {
+    pub effect: Effect,
+    pub turns: i32,
+    // in percent
+    pub chance: u32,
+}
+
+impl OnHit {
+    pub fn validate(&self) -> Result<(), String> {
+        self.effect.validate()?;
+        if self.turns <= 0 {
+            return Err(format!("on_hit turns must be positive, got {}", self.turns));
+        }
+        if self.chance == 0 || self.chance > 100 {
+            return Err(format!("on_hit chance must be between 1 and 100, got {}", self.chance));
+        }
+        Ok(())
+    }
+}
+
+// Start an effect. If the same kind is already running, the new one replaces it
+// but it lasts at least as long as the old one had left.
+pub fn add_effect(game_object: &mut GameObject, status_effect: StatusEffect, game: &mut Game) {
+    let effect = status_effect.effect;
+    if let Some(running) = game_object.effects.iter_mut().find(|running| running.effect.is_same_kind(effect)) {
+        running.effect = effect;
+        running.turns = running.turns.max(status_effect.turns);
+        return;
+    }
+    game_object.effects.push(status_effect);
+    if game.fov.is_in_fov(game_object.x, game_object.y) {
+        game.messages.add(format!("{} is {}!", game_object.name, effect.adjective()), effect.color());
+    }
+}
+
+// Everything a fighter's effects do when its turn comes up: poison, regeneration, and one turn less
+// for each of them. Returns false if it can't act this turn, because it's stunned or has just died.
+pub fn start_turn(index: usize, game: &mut Game, game_objects: &mut [GameObject]) -> bool {
+    if game_objects[index].effects.is_empty() {
+        return true;
+    }
+    let visible = game.fov.is_in_fov(game_objects[index].x, game_objects[index].y);
+    let stunned = game_objects[index].is_stunned();
+
+    for status_effect in game_objects[index].effects.clone() {
+        match status_effect.effect {
+            Effect::Poison(damage) => {
+                if visible {
+                    game.messages.add(
+                        format!("{} takes {} poison damage.", game_objects[index].name, damage),
+                        GREEN,
+                    );
+                }
+                if let Some(xp) = game_objects[index].take_damage(damage, game) {
+                    if status_effect.from_player && index != PLAYER {
+                        game_objects[PLAYER].gain_xp(xp);
+                    }
+                }
+            }
+            Effect::Regeneration(amount) => game_objects[index].heal(amount, game),
+            Effect::Stun | Effect::Haste => {}
+        }
+    }
+    if game_objects[index].fighter.is_none() {
+        // died of it; a corpse has no effects left
+        game_objects[index].effects.clear();
+        return false;
+    }
+
+    for status_effect in game_objects[index].effects.iter_mut() {
+        status_effect.turns -= 1;
+    }
+    let game_object = &mut game_objects[index];
+    for expired in game_object.effects.iter().filter(|status_effect| status_effect.turns <= 0) {
+        if visible {
+            game.messages.add(
+                format!("{} is no longer {}.", game_object.name, expired.effect.adjective()),
+                WHITE,
+            );
+        }
+    }
+    game_object.effects.retain(|status_effect| status_effect.turns > 0);
+
+    !stunned
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_game, end_player_turn, MAP_HEIGHT, MAP_WIDTH};
+    use crate::monsters::builtin_monsters;
+    use crate::scheduler::{Actor, NORMAL_SPEED};
+    use crate::tile::Tile;
+    use crate::PLAYER;
+    use super::*;
+
+    // the player alone in an open room
+    fn open_room() -> (Game, Vec<GameObject>) {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        game.map = vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        game_objects.truncate(1);
+        game_objects[PLAYER].set_position(10, 10);
+        game.update_fov(&game_objects);
+        (game, game_objects)
+    }
+
+    fn hp(game_object: &GameObject) -> i32 {
+        game_object.fighter.map_or(0, |fighter| fighter.hp)
+    }
+
+    fn texts(game: &Game) -> Vec<String> {
+        game.messages.iter().map(|(text, _)| text).collect()
+    }
+
+    #[test]
+    fn poison_hurts_every_turn_until_it_runs_out() {
+        let (mut game, mut game_objects) = open_room();
+        add_effect(&mut game_objects[PLAYER], StatusEffect { effect: Effect::Poison(2), turns: 3, from_player: false }, &mut game);
+        for _ in 0..5 {
+            assert!(start_turn(PLAYER, &mut game, &mut game_objects));
+        }
+        assert_eq!(hp(&game_objects[PLAYER]), 30 - 3 * 2);
+        assert!(game_objects[PLAYER].effects.is_empty());
+        let texts = texts(&game);
+        assert!(texts.contains(&"Player is poisoned!".to_string()));
+        assert_eq!(texts.last().unwrap(), "Player is no longer poisoned.");
+    }
+
+    #[test]
+    fn regeneration_heals_up_to_the_maximum() {
+        let (mut game, mut game_objects) = open_room();
+        game_objects[PLAYER].fighter.as_mut().unwrap().hp = 25;
+        add_effect(&mut game_objects[PLAYER], StatusEffect { effect: Effect::Regeneration(2), turns: 10, from_player: false }, &mut game);
+        start_turn(PLAYER, &mut game, &mut game_objects);
+        assert_eq!(hp(&game_objects[PLAYER]), 27);
+        for _ in 0..5 {
+            start_turn(PLAYER, &mut game, &mut game_objects);
+        }
+        assert_eq!(hp(&game_objects[PLAYER]), 30);
+    }
+
+    #[test]
+    fn adding_an_effect_again_keeps_the_longer_one() {
+        let (mut game, mut game_objects) = open_room();
+        add_effect(&mut game_objects[PLAYER], StatusEffect { effect: Effect::Poison(1), turns: 5, from_player: false }, &mut game);
+        add_effect(&mut game_objects[PLAYER], StatusEffect { effect: Effect::Poison(3), turns: 2, from_player: false }, &mut game);
+        assert_eq!(game_objects[PLAYER].effects, vec![StatusEffect { effect: Effect::Poison(3), turns: 5, from_player: false }]);
+    }
+
+    #[test]
+    fn poison_can_kill() {
+        let (mut game, mut game_objects) = open_room();
+        game_objects.push(builtin_monsters()[0].spawn(12, 10));
+        add_effect(&mut game_objects[1], StatusEffect { effect: Effect::Poison(20), turns: 3, from_player: false }, &mut game);
+        assert!(!start_turn(1, &mut game, &mut game_objects));
+        assert!(game_objects[1].fighter.is_none());
+        assert!(game_objects[1].effects.is_empty());
+        assert_eq!(game.last_corpse, Some((12, 10)));
+    }
+
+    #[test]
+    fn a_kill_by_the_players_poison_is_worth_experience() {
+        let (mut game, mut game_objects) = open_room();
+        game_objects.push(builtin_monsters()[0].spawn(12, 10));
+        game_objects.push(builtin_monsters()[0].spawn(14, 10));
+        let orc_xp = game_objects[1].fighter.unwrap().xp;
+        add_effect(&mut game_objects[1], StatusEffect { effect: Effect::Poison(20), turns: 3, from_player: true }, &mut game);
+        add_effect(&mut game_objects[2], StatusEffect { effect: Effect::Poison(20), turns: 3, from_player: false }, &mut game);
+        let xp_before = game_objects[PLAYER].fighter.unwrap().xp;
+
+        start_turn(1, &mut game, &mut game_objects);
+        assert_eq!(game_objects[PLAYER].fighter.unwrap().xp, xp_before + orc_xp);
+        // poison from anyone else earns the player nothing
+        start_turn(2, &mut game, &mut game_objects);
+        assert!(game_objects[2].fighter.is_none());
+        assert_eq!(game_objects[PLAYER].fighter.unwrap().xp, xp_before + orc_xp);
+    }
+
+    #[test]
+    fn a_stunned_player_loses_their_turn() {
+        let (mut game, mut game_objects) = open_room();
+        game_objects.push(builtin_monsters()[0].spawn(11, 10));
+        add_effect(&mut game_objects[PLAYER], StatusEffect { effect: Effect::Stun, turns: 1, from_player: false }, &mut game);
+
+        // the orc attacks twice: once after the player's move and once while they're stunned
+        end_player_turn(&mut game, &mut game_objects);
+        assert_eq!(game.messages.count_starting_with("Orc attacks Player"), 2);
+        assert!(!game_objects[PLAYER].is_stunned());
+    }
+
+    #[test]
+    fn a_stunned_monster_does_nothing() {
+        let (mut game, mut game_objects) = open_room();
+        game_objects.push(builtin_monsters()[0].spawn(11, 10));
+        add_effect(&mut game_objects[1], StatusEffect { effect: Effect::Stun, turns: 2, from_player: false }, &mut game);
+        end_player_turn(&mut game, &mut game_objects);
+        end_player_turn(&mut game, &mut game_objects);
+        assert_eq!(game.messages.count_starting_with("Orc attacks Player"), 0);
+        end_player_turn(&mut game, &mut game_objects);
+        assert_eq!(game.messages.count_starting_with("Orc attacks Player"), 1);
+    }
+
+    #[test]
+    fn haste_doubles_speed_while_it_lasts() {
+        let (mut game, mut game_objects) = open_room();
+        add_effect(&mut game_objects[PLAYER], StatusEffect { effect: Effect::Haste, turns: 2, from_player: false }, &mut game);
+        assert_eq!(game_objects[PLAYER].speed(), 2 * NORMAL_SPEED);
+        start_turn(PLAYER, &mut game, &mut game_objects);
+        start_turn(PLAYER, &mut game, &mut game_objects);
+        assert_eq!(game_objects[PLAYER].speed(), NORMAL_SPEED);
+    }
+}
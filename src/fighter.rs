@@ -1,21 +1,40 @@
-use tcod::colors::{DARK_RED, LIGHT_BLUE, ORANGE, RED};
+use serde::{Deserialize, Serialize};
+use crate::colors::{DARK_RED, LIGHT_BLUE, ORANGE, RED};
 use crate::{Game, GameObject};
+use crate::effects::OnHit;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Fighter {
-    pub max_hp: i32,
+    // Base stats only; use GameObject::max_hp / defense / power to include equipment bonuses.
+    pub base_max_hp: i32,
     pub hp: i32,
-    pub defense: i32,
-    pub power: i32,
+    pub base_defense: i32,
+    pub base_power: i32,
+    // Energy gained per tick, see the scheduler.
+    pub base_speed: i32,
+    // To-hit rolls, see combat: accuracy makes its attacks land more often, evasion makes it harder to hit.
+    pub accuracy: i32,
+    pub evasion: i32,
+    // For the player: experience gathered so far. For monsters: experience awarded for killing them.
+    pub xp: i32,
     pub on_death: DeathCallback,
+    // what its attacks can do besides damage
+    pub on_hit: Option<OnHit>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Ai {
     Basic,
+    // Keeps its distance and shoots at the player.
+    Archer,
+    // Wanders around randomly for a number of turns, then goes back to its previous AI.
+    Confused {
+        previous_ai: Box<Ai>,
+        num_turns: i32,
+    },
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum DeathCallback {
     Player,
     Monster,
@@ -34,7 +53,7 @@ impl DeathCallback {
 
 fn player_death(player: &mut GameObject, game: &mut Game) {
     game.messages.add("You died!", RED);
-    game.messages.add("Press ESC to QUIT.", LIGHT_BLUE);
+    game.messages.add("Press any key to return to the main menu.", LIGHT_BLUE);
     // Make the player a corpse.
     player.char = '%';
     player.color = DARK_RED;
@@ -48,4 +67,6 @@ fn monster_death(monster: &mut GameObject, game: &mut Game) {
     monster.fighter = None;
     monster.ai = None;
     monster.name = format!("The remains of {}", monster.name);
+    // remember where it fell, so the player can find their way back
+    game.last_corpse = Some(monster.get_position());
 }
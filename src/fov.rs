@@ -0,0 +1,424 @@
+use crate::game::Map;
+
+pub const SIGHT_RADIUS: i32 = 10;
+
+// The octants used by recursive shadowcasting, as multipliers that turn
+// (column, row) offsets into map offsets: [xx, xy, yx, yy] for each octant.
+const OCTANTS: [[i32; 4]; 8] = [
+    [1, 0, 0, 1],
+    [0, 1, 1, 0],
+    [0, -1, 1, 0],
+    [-1, 0, 0, 1],
+    [-1, 0, 0, -1],
+    [0, -1, -1, 0],
+    [0, 1, -1, 0],
+    [1, 0, 0, -1],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FovAlgorithm {
+    // Classic recursive shadowcasting. Fast and good looking, but not symmetric:
+    // a monster may see the player from a tile the player can't see.
+    Shadowcasting,
+    // Symmetric shadowcasting: a floor tile is visible from another exactly when
+    // the other is visible from it, so "if you can see it, it can see you" holds.
+    Symmetric,
+}
+
+// What the player can currently see. It's computed straight from the game map,
+// so tiles that change (a door opening, a wall being dug out) show up the next time it's computed.
+pub struct Fov {
+    pub algorithm: FovAlgorithm,
+    pub radius: i32,
+    visible: Vec<Vec<bool>>,
+}
+
+impl Fov {
+    pub fn new(algorithm: FovAlgorithm, radius: i32) -> Self {
+        Fov { algorithm, radius, visible: vec![] }
+    }
+
+    pub fn compute(&mut self, map: &Map, x: i32, y: i32) {
+        // start from nothing visible, sized to the current map
+        let height = map.first().map_or(0, |column| column.len());
+        self.visible = vec![vec![false; height]; map.len()];
+        if !in_bounds(map, x, y) {
+            return;
+        }
+
+        // you can always see where you stand
+        self.visible[x as usize][y as usize] = true;
+        match self.algorithm {
+            FovAlgorithm::Shadowcasting => {
+                for octant in OCTANTS.iter() {
+                    self.cast_light(map, (x, y), 1, 1.0, 0.0, octant);
+                }
+            }
+            FovAlgorithm::Symmetric => {
+                for quadrant in [Quadrant::North, Quadrant::East, Quadrant::South, Quadrant::West] {
+                    let first_row = Row { depth: 1, start_slope: Slope::new(-1, 1), end_slope: Slope::new(1, 1) };
+                    self.scan(map, (x, y), quadrant, first_row);
+                }
+            }
+        }
+    }
+
+    pub fn is_in_fov(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 {
+            return false;
+        }
+        self.visible
+            .get(x as usize)
+            .and_then(|column| column.get(y as usize))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    fn within_radius(&self, dx: i32, dy: i32) -> bool {
+        dx * dx + dy * dy <= self.radius * self.radius
+    }
+
+    fn reveal(&mut self, map: &Map, x: i32, y: i32) {
+        if in_bounds(map, x, y) {
+            self.visible[x as usize][y as usize] = true;
+        }
+    }
+
+    // Recursive shadowcasting over one octant, scanning rows outwards from the origin
+    // and recursing whenever a run of blocking tiles starts a new shadow.
+    fn cast_light(
+        &mut self,
+        map: &Map,
+        origin: (i32, i32),
+        first_row: i32,
+        mut start_slope: f32,
+        end_slope: f32,
+        octant: &[i32; 4],
+    ) {
+        if start_slope < end_slope {
+            return;
+        }
+        let [xx, xy, yx, yy] = *octant;
+        for row in first_row..=self.radius {
+            let dy = -row;
+            let mut blocked = false;
+            let mut next_start_slope = start_slope;
+            for dx in -row..=0 {
+                let x = origin.0 + dx * xx + dy * xy;
+                let y = origin.1 + dx * yx + dy * yy;
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start_slope < right_slope {
+                    continue;
+                } else if end_slope > left_slope {
+                    break;
+                }
+
+                if self.within_radius(dx, dy) {
+                    self.reveal(map, x, y);
+                }
+
+                let tile_blocks = blocks_sight(map, x, y);
+                if blocked {
+                    if tile_blocks {
+                        // still in the shadow
+                        next_start_slope = right_slope;
+                    } else {
+                        // out of the shadow, carry on from where it ended
+                        blocked = false;
+                        start_slope = next_start_slope;
+                    }
+                } else if tile_blocks && row < self.radius {
+                    // a new shadow starts: scan what's left of the light beyond it
+                    blocked = true;
+                    self.cast_light(map, origin, row + 1, start_slope, left_slope, octant);
+                    next_start_slope = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+
+    // Symmetric shadowcasting over one quadrant, one row at a time.
+    fn scan(&mut self, map: &Map, origin: (i32, i32), quadrant: Quadrant, mut row: Row) {
+        if row.depth > self.radius {
+            return;
+        }
+        let mut previous: Option<bool> = None; // whether the previous tile in the row was a wall
+        for column in row.min_column()..=row.max_column() {
+            let (x, y) = quadrant.transform(origin, row.depth, column);
+            let is_wall = blocks_sight(map, x, y);
+            let (dx, dy) = (x - origin.0, y - origin.1);
+            if (is_wall || row.is_symmetric(column)) && self.within_radius(dx, dy) {
+                self.reveal(map, x, y);
+            }
+            if previous == Some(true) && !is_wall {
+                row.start_slope = Slope::of_tile(row.depth, column);
+            }
+            if previous == Some(false) && is_wall {
+                let mut next_row = row.next();
+                next_row.end_slope = Slope::of_tile(row.depth, column);
+                self.scan(map, origin, quadrant, next_row);
+            }
+            previous = Some(is_wall);
+        }
+        if previous == Some(false) {
+            self.scan(map, origin, quadrant, row.next());
+        }
+    }
+}
+
+impl Default for Fov {
+    fn default() -> Self {
+        Fov::new(FovAlgorithm::Shadowcasting, SIGHT_RADIUS)
+    }
+}
+
+fn in_bounds(map: &Map, x: i32, y: i32) -> bool {
+    x >= 0 && y >= 0 && (x as usize) < map.len() && (y as usize) < map[x as usize].len()
+}
+
+// anything outside the map blocks sight too
+fn blocks_sight(map: &Map, x: i32, y: i32) -> bool {
+    !in_bounds(map, x, y) || map[x as usize][y as usize].is_sight_blocked
+}
+
+// The four directions symmetric shadowcasting scans in.
+#[derive(Clone, Copy)]
+enum Quadrant {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Quadrant {
+    // map coordinates of the tile at the given row (distance from the origin) and column
+    fn transform(self, origin: (i32, i32), row: i32, column: i32) -> (i32, i32) {
+        let (x, y) = origin;
+        match self {
+            Quadrant::North => (x + column, y - row),
+            Quadrant::South => (x + column, y + row),
+            Quadrant::East => (x + row, y + column),
+            Quadrant::West => (x - row, y + column),
+        }
+    }
+}
+
+// An exact fraction, so the symmetric algorithm doesn't suffer from rounding errors.
+#[derive(Clone, Copy)]
+struct Slope {
+    numerator: i32,
+    denominator: i32, // always positive
+}
+
+impl Slope {
+    fn new(numerator: i32, denominator: i32) -> Self {
+        Slope { numerator, denominator }
+    }
+
+    // the slope through the near edge of a tile
+    fn of_tile(depth: i32, column: i32) -> Self {
+        Slope::new(2 * column - 1, 2 * depth)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Row {
+    depth: i32,
+    start_slope: Slope,
+    end_slope: Slope,
+}
+
+impl Row {
+    fn min_column(&self) -> i32 {
+        // round depth * start_slope, with ties going up
+        let Slope { numerator, denominator } = self.start_slope;
+        (2 * self.depth * numerator + denominator).div_euclid(2 * denominator)
+    }
+
+    fn max_column(&self) -> i32 {
+        // round depth * end_slope, with ties going down
+        let Slope { numerator, denominator } = self.end_slope;
+        -(-(2 * self.depth * numerator - denominator)).div_euclid(2 * denominator)
+    }
+
+    fn is_symmetric(&self, column: i32) -> bool {
+        column * self.start_slope.denominator >= self.depth * self.start_slope.numerator
+            && column * self.end_slope.denominator <= self.depth * self.end_slope.numerator
+    }
+
+    fn next(&self) -> Row {
+        Row { depth: self.depth + 1, ..*self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tile::{map_from, Tile};
+    use super::*;
+
+    // Draw what's visible the same way: '*' for visible tiles, ' ' for the rest.
+    fn visible_tiles(fov: &Fov, map: &Map) -> Vec<String> {
+        (0..map[0].len() as i32)
+            .map(|y| {
+                (0..map.len() as i32)
+                    .map(|x| if fov.is_in_fov(x, y) { '*' } else { ' ' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn compute(algorithm: FovAlgorithm, rows: &[&str], x: i32, y: i32) -> Vec<String> {
+        let map = map_from(rows);
+        let mut fov = Fov::new(algorithm, SIGHT_RADIUS);
+        fov.compute(&map, x, y);
+        visible_tiles(&fov, &map)
+    }
+
+    const ROOM: [&str; 5] = [
+        "#######",
+        "#.....#",
+        "#.....#",
+        "#.....#",
+        "#######",
+    ];
+
+    const PILLAR: [&str; 7] = [
+        "#########",
+        "#.......#",
+        "#...#...#",
+        "#.......#",
+        "#.......#",
+        "#.......#",
+        "#########",
+    ];
+
+    const CORRIDOR: [&str; 5] = [
+        "#########",
+        "#...#####",
+        "#........",
+        "#...#####",
+        "#########",
+    ];
+
+    const DOOR: [&str; 5] = [
+        "#########",
+        "#...#...#",
+        "#...#...#",
+        "#...#...#",
+        "#########",
+    ];
+
+    #[test]
+    fn whole_room_is_visible_including_walls() {
+        for algorithm in [FovAlgorithm::Shadowcasting, FovAlgorithm::Symmetric] {
+            assert_eq!(compute(algorithm, &ROOM, 3, 2), vec!["*******"; 5], "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn shadowcasting_pillar_casts_a_shadow() {
+        let expected = vec![
+            "*********",
+            "*********",
+            "*********",
+            "**** ****",
+            "***   ***",
+            "**     **",
+            "*       *",
+        ];
+        assert_eq!(compute(FovAlgorithm::Shadowcasting, &PILLAR, 4, 1), expected);
+    }
+
+    #[test]
+    fn symmetric_pillar_casts_a_shadow() {
+        let expected = vec![
+            "*********",
+            "*********",
+            "*********",
+            "**** ****",
+            "***   ***",
+            "***   ***",
+            "**     **",
+        ];
+        assert_eq!(compute(FovAlgorithm::Symmetric, &PILLAR, 4, 1), expected);
+    }
+
+    #[test]
+    fn corridor_limits_the_view() {
+        // the walls along the corridor are lit, the ones behind them aren't
+        let expected = vec![
+            "*****    ",
+            "*********",
+            "*********",
+            "*********",
+            "*****    ",
+        ];
+        for algorithm in [FovAlgorithm::Shadowcasting, FovAlgorithm::Symmetric] {
+            assert_eq!(compute(algorithm, &CORRIDOR, 2, 2), expected, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn sight_is_limited_by_the_radius() {
+        let map = vec![vec![Tile::empty(); 30]; 30];
+        for algorithm in [FovAlgorithm::Shadowcasting, FovAlgorithm::Symmetric] {
+            let mut fov = Fov::new(algorithm, 3);
+            fov.compute(&map, 10, 10);
+            assert!(fov.is_in_fov(13, 10));
+            assert!(!fov.is_in_fov(14, 10));
+            assert!(fov.is_in_fov(12, 12));
+            assert!(!fov.is_in_fov(13, 13));
+        }
+    }
+
+    #[test]
+    fn symmetric_fov_is_symmetric_between_floor_tiles() {
+        let rows = [
+            "##########",
+            "#........#",
+            "#..#...#.#",
+            "#....#...#",
+            "#.#......#",
+            "#.....#..#",
+            "##########",
+        ];
+        let map = map_from(&rows);
+        let floor: Vec<(i32, i32)> = (0..map.len() as i32)
+            .flat_map(|x| (0..map[0].len() as i32).map(move |y| (x, y)))
+            .filter(|&(x, y)| !map[x as usize][y as usize].is_blocked)
+            .collect();
+
+        let mut from = Fov::new(FovAlgorithm::Symmetric, SIGHT_RADIUS);
+        let mut to = Fov::new(FovAlgorithm::Symmetric, SIGHT_RADIUS);
+        for &(x1, y1) in &floor {
+            from.compute(&map, x1, y1);
+            for &(x2, y2) in &floor {
+                to.compute(&map, x2, y2);
+                assert_eq!(
+                    from.is_in_fov(x2, y2),
+                    to.is_in_fov(x1, y1),
+                    "({}, {}) and ({}, {})",
+                    x1, y1, x2, y2
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn changed_tiles_are_picked_up_on_the_next_compute() {
+        let mut map = map_from(&DOOR);
+        let mut fov = Fov::default();
+        fov.compute(&map, 2, 2);
+        assert!(!fov.is_in_fov(6, 2));
+
+        // knock a hole in the wall, like opening a door
+        map[4][2] = Tile::empty();
+        fov.compute(&map, 2, 2);
+        assert!(fov.is_in_fov(4, 2));
+        assert!(fov.is_in_fov(6, 2));
+    }
+}
@@ -1,15 +1,34 @@
 use std::cmp;
 use rand::Rng;
-use tcod::colors;
-use crate::{Ai, DeathCallback, Fighter, GameObject, is_blocked, MAP_HEIGHT, MAP_WIDTH, move_toward, PLAYER, Tcod};
+use serde::{Deserialize, Serialize};
+use crate::colors;
+use crate::{Ai, DeathCallback, Fighter, GameObject, is_blocked, MAP_HEIGHT, MAP_WIDTH, move_by, move_toward, PLAYER, squeezes_between_walls};
+use crate::activity::Activity;
+use crate::dijkstra::DijkstraMap;
+use crate::effects::start_turn;
+use crate::fov::Fov;
+use crate::item::{Equipment, Item, Slot};
 use crate::messages::Messages;
+use crate::pathfinding::NEIGHBOURS;
+use crate::ranged::{can_shoot, fire, Projectile, ARROW_RANGE, BOW_POWER};
+use crate::monsters::MonsterTemplate;
 use crate::tile::*;
 use crate::rect::*;
+use crate::rng::GameRng;
+use crate::scheduler::{Scheduler, NORMAL_SPEED};
+use crate::spawn::*;
 
 const ROOM_MAX_SIZE: i32 = 10;
 const ROOM_MIN_SIZE: i32 = 6;
 const MAX_ROOMS: i32 = 30;
-const MAX_ROOM_MONSTERS: i32 = 3;
+// Archers back off when the player gets closer than this.
+const ARCHER_KEEP_DISTANCE: f32 = 3.0;
+// Below this share of its hit points, in percent, a basic monster runs from the player instead of fighting.
+const FLEE_HP_PERCENT: i32 = 25;
+// Fleeing monsters would rather go around each other than wait in line.
+const CROWDED_TILE_COST: i32 = 5;
+// What an arrow looks like in flight.
+pub const ARROW: (char, colors::Color) = ('*', colors::SEPIA);
 
 pub type Map = Vec<Vec<Tile>>;
 
@@ -21,12 +40,170 @@ pub enum PlayerAction {
     Exit,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Game {
     pub map: Map,
     pub messages: Messages,
+    pub inventory: Vec<GameObject>,
+    pub dungeon_level: u32,
+    // The seed the dungeon was generated from, and the generator all random rolls come from.
+    pub seed: u64,
+    // where the last monster on this level died, for travelling back to it
+    pub last_corpse: Option<(i32, i32)>,
+    pub rng: GameRng,
+    // Loaded at startup rather than saved, so edits to the monsters file apply to continued games too.
+    #[serde(skip)]
+    pub monster_templates: Vec<MonsterTemplate>,
+    // Recomputed from the map instead of saved.
+    #[serde(skip)]
+    pub fov: Fov,
+    // What the player is doing over several turns, like exploring.
+    #[serde(skip)]
+    pub activity: Option<Activity>,
+    // Shot this turn, waiting to be shown flying across the map.
+    #[serde(skip)]
+    pub projectiles: Vec<Projectile>,
+    // For monsters running away; built at the end of each of the player's turns.
+    #[serde(skip)]
+    pub flee_maps: Option<FleeMaps>,
 }
 
-pub fn make_map(game_objects: &mut Vec<GameObject>) -> Map {
+// How far every tile is from the player, going around other monsters, and the way away from them.
+pub struct FleeMaps {
+    player: DijkstraMap,
+    flee: DijkstraMap,
+}
+
+impl FleeMaps {
+    fn new() -> Self {
+        FleeMaps {
+            player: DijkstraMap::new(MAP_WIDTH, MAP_HEIGHT),
+            flee: DijkstraMap::new(MAP_WIDTH, MAP_HEIGHT),
+        }
+    }
+}
+
+impl Game {
+    // Recompute what the player can see, and remember every tile they have seen.
+    pub fn update_fov(&mut self, game_objects: &[GameObject]) {
+        let (player_x, player_y) = game_objects[PLAYER].get_position();
+        self.fov.compute(&self.map, player_x, player_y);
+        for (x, column) in self.map.iter_mut().enumerate() {
+            for (y, tile) in column.iter_mut().enumerate() {
+                if self.fov.is_in_fov(x as i32, y as i32) {
+                    tile.is_explored = true;
+                }
+            }
+        }
+    }
+}
+
+// Set up a fresh game on the first dungeon level. No window is needed for this,
+// so tests can create a game the same way the main menu does.
+pub fn create_game(monster_templates: &[MonsterTemplate], seed: u64) -> (Game, Vec<GameObject>) {
+    // Create player and add to list of game objects. Position will be set in 'make_map(...)'.
+    let mut player = GameObject::new(0, 0, '@', "Player", colors::WHITE, false);
+    player.alive = true;
+    player.fighter = Some(Fighter {
+        base_max_hp: 30,
+        hp: 30,
+        base_defense: 2,
+        base_power: 5,
+        base_speed: NORMAL_SPEED,
+        accuracy: 0,
+        evasion: 0,
+        xp: 0,
+        on_death: DeathCallback::Player,
+        on_hit: None,
+    });
+    let mut game_objects = vec![];
+    game_objects.push(player);
+
+    let level = 1;
+    let mut rng = GameRng::new(seed);
+    let mut game = Game {
+        // generate map (at this point it's not drawn to the screen)
+        map: make_map(&mut game_objects, monster_templates, level, &mut rng),
+        messages: Messages::new(),
+        inventory: vec![],
+        dungeon_level: level,
+        seed,
+        last_corpse: None,
+        rng,
+        monster_templates: monster_templates.to_vec(),
+        fov: Fov::default(),
+        activity: None,
+        projectiles: vec![],
+        flee_maps: None,
+    };
+
+    game.update_fov(&game_objects);
+
+    // a warm welcoming message
+    game.messages.add(
+        "Welcome to the dungeon! Prepare to die.",
+        colors::RED,
+    );
+
+    (game, game_objects)
+}
+
+// Advance to the next level
+pub fn next_level(game: &mut Game, game_objects: &mut Vec<GameObject>) {
+    game.messages.add(
+        "You take a moment to rest, and recover your strength.",
+        colors::VIOLET,
+    );
+    let heal_hp = game_objects[PLAYER].max_hp(game) / 2;
+    game_objects[PLAYER].heal(heal_hp, game);
+
+    game.messages.add(
+        "After a rare moment of peace, you descend deeper into \
+         the heart of the dungeon...",
+        colors::RED,
+    );
+    game.dungeon_level += 1;
+    game.map = make_map(game_objects, &game.monster_templates, game.dungeon_level, &mut game.rng);
+    game.last_corpse = None;
+    game.update_fov(game_objects);
+    game.messages.add(
+        format!("Welcome to level {} of the dungeon.", game.dungeon_level),
+        colors::LIGHT_BLUE,
+    );
+}
+
+// Everything that happens once the player has used up their turn:
+// update what the player sees from their new position, then let every monster act,
+// as often as its speed allows, until the player has the energy for another turn.
+// Each turn starts with the effects on whoever's turn it is; a stunned player doesn't get theirs.
+pub fn end_player_turn(game: &mut Game, game_objects: &mut [GameObject]) {
+    game.update_fov(game_objects);
+    update_flee_maps(game, game_objects);
+    let scheduler = Scheduler::default();
+    scheduler.spend(&mut game_objects[PLAYER]);
+    while game_objects[PLAYER].alive {
+        let index = match scheduler.next_actor(game_objects) {
+            Some(index) => index,
+            None => break,
+        };
+        let can_act = start_turn(index, game, game_objects);
+        if index == PLAYER {
+            if can_act || !game_objects[PLAYER].alive {
+                break;
+            }
+        } else if can_act && game_objects[index].ai.is_some() {
+            ai_take_turn(index, game, game_objects);
+        }
+        scheduler.spend(&mut game_objects[index]);
+    }
+}
+
+pub fn make_map(
+    game_objects: &mut Vec<GameObject>,
+    monster_templates: &[MonsterTemplate],
+    level: u32,
+    rng: &mut GameRng,
+) -> Map {
     // fill the map with blocked tiles.
     // The vec! macro is a shortcut that creates a Vec and fills it with values. For example, vec!['a'; 42] would create a Vec containing the letter 'a' 42 times. We do the same trick above to build a column of tiles and then build the map of those columns.
     let width = MAP_WIDTH as usize;
@@ -34,17 +211,22 @@ pub fn make_map(game_objects: &mut Vec<GameObject>) -> Map {
     let tile = Tile::wall();
     let mut map = vec![vec![tile; height]; width];
 
+    // Player is the first element, remove everything else.
+    // NOTE: works only when the player is the first object!
+    assert_eq!(&game_objects[PLAYER] as *const _, &game_objects[0] as *const _);
+    game_objects.truncate(1);
+
     // rooms
     let mut rooms = vec![];
 
     // generate rooms
     for _ in 0..MAX_ROOMS {
         // random width & height
-        let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-        let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+        let w = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+        let h = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
         // random location
-        let x = rand::thread_rng().gen_range(0, MAP_WIDTH - w);
-        let y = rand::thread_rng().gen_range(0, MAP_HEIGHT - h);
+        let x = rng.gen_range(0, MAP_WIDTH - w);
+        let y = rng.gen_range(0, MAP_HEIGHT - h);
         // create room
         let new_room = Rect::new(x, y, w, h);
         // check if it intersects with any other room, and if not, add it to rooms vec
@@ -55,7 +237,7 @@ pub fn make_map(game_objects: &mut Vec<GameObject>) -> Map {
             // create room
             create_room(new_room, &mut map);
             // create monsters in room
-            place_objects(new_room, &map, game_objects);
+            place_objects(new_room, &map, game_objects, monster_templates, level, rng);
 
             // this will be useful later
             let (new_x, new_y) = new_room.center();
@@ -68,7 +250,7 @@ pub fn make_map(game_objects: &mut Vec<GameObject>) -> Map {
                 // connect to previous room with a tunnel
                 let (prev_x, prev_y) = rooms[rooms.len() - 1].center();
 
-                if rand::random() {
+                if rng.gen() {
                     create_h_tunnel(prev_x, new_x, prev_y, &mut map);
                     create_v_tunnel(prev_y, new_y, new_x, &mut map);
                 } else {
@@ -80,6 +262,13 @@ pub fn make_map(game_objects: &mut Vec<GameObject>) -> Map {
             rooms.push(new_room)
         }
     }
+
+    // create stairs at the center of the last room
+    let (last_room_x, last_room_y) = rooms[rooms.len() - 1].center();
+    let mut stairs = GameObject::new(last_room_x, last_room_y, '<', "stairs", colors::WHITE, false);
+    stairs.always_visible = true;
+    game_objects.push(stairs);
+
     map
 }
 
@@ -116,55 +305,175 @@ fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
     }
 }
 
-fn place_objects(room: Rect, map: &Map, game_objects: &mut Vec<GameObject>) {
+fn place_objects(
+    room: Rect,
+    map: &Map,
+    game_objects: &mut Vec<GameObject>,
+    monster_templates: &[MonsterTemplate],
+    level: u32,
+    rng: &mut GameRng,
+) {
     // choose random amount of monsters
-    let num_monsters = rand::thread_rng().gen_range(0, MAX_ROOM_MONSTERS + 1);
+    let num_monsters = rng.gen_range(0, max_room_monsters(level) + 1);
+    let monster_chances = monster_table(monster_templates, level);
 
     for _ in 0..num_monsters {
         // choose a random spot for this monster
-        let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-        let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
+        let x = rng.gen_range(room.x1 + 1, room.x2);
+        let y = rng.gen_range(room.y1 + 1, room.y2);
 
         // check if the spot is occupied, if not, create the monster
         let occupied = is_blocked(x, y, map, game_objects);
         if !occupied {
-            // Calling rand::random::<f32>() will produce an f32 number between 0.0 and 1.0
-            let mut monster = if rand::random::<f32>() < 0.8 {
-                // 80% chance of an Ork
-                let mut orc = GameObject::new(x, y, 'o', "Orc", colors::DESATURATED_GREEN, true);
-                orc.fighter = Some(Fighter {
-                    max_hp: 10,
-                    hp: 10,
-                    defense: 0,
-                    power: 3,
-                    on_death: DeathCallback::Monster,
-                });
-                orc.ai = Some(Ai::Basic);
-                orc
-            } else {
-                // 20% chance of a Troll
-                let mut troll = GameObject::new(x, y, 'T', "Troll", colors::DARKER_GREEN, true);
-                troll.fighter = Some(Fighter {
-                    max_hp: 16,
-                    hp: 16,
-                    defense: 1,
-                    power: 4,
-                    on_death: DeathCallback::Monster,
-                });
-                troll.ai = Some(Ai::Basic);
-                troll
+            if let Some(template) = weighted_choice(&monster_chances, rng) {
+                game_objects.push(template.spawn(x, y));
+            }
+        }
+    }
+
+    // choose random number of items
+    let num_items = rng.gen_range(0, max_room_items(level) + 1);
+    let item_chances = item_table(level);
+
+    for _ in 0..num_items {
+        // choose random spot for this item
+        let x = rng.gen_range(room.x1 + 1, room.x2);
+        let y = rng.gen_range(room.y1 + 1, room.y2);
+
+        // only place it if the tile is not blocked
+        if !is_blocked(x, y, map, game_objects) {
+            let item = match weighted_choice(&item_chances, rng) {
+                Some(Item::Heal) => {
+                    // create a healing potion
+                    let mut potion = GameObject::new(x, y, '!', "healing potion", colors::VIOLET, false);
+                    potion.item = Some(Item::Heal);
+                    potion
+                }
+                Some(Item::Regeneration) => {
+                    // create a regeneration potion
+                    let mut potion = GameObject::new(x, y, '!', "potion of regeneration", colors::LIGHT_VIOLET, false);
+                    potion.item = Some(Item::Regeneration);
+                    potion
+                }
+                Some(Item::Haste) => {
+                    // create a haste potion
+                    let mut potion = GameObject::new(x, y, '!', "potion of haste", colors::LIGHT_BLUE, false);
+                    potion.item = Some(Item::Haste);
+                    potion
+                }
+                Some(Item::Lightning) => {
+                    // create a lightning bolt scroll
+                    let mut scroll = GameObject::new(x, y, '#', "scroll of lightning bolt", colors::LIGHT_YELLOW, false);
+                    scroll.item = Some(Item::Lightning);
+                    scroll
+                }
+                Some(Item::Fireball) => {
+                    // create a fireball scroll
+                    let mut scroll = GameObject::new(x, y, '#', "scroll of fireball", colors::LIGHT_YELLOW, false);
+                    scroll.item = Some(Item::Fireball);
+                    scroll
+                }
+                Some(Item::Confuse) => {
+                    // create a confuse scroll
+                    let mut scroll = GameObject::new(x, y, '#', "scroll of confusion", colors::LIGHT_YELLOW, false);
+                    scroll.item = Some(Item::Confuse);
+                    scroll
+                }
+                Some(Item::Bow) => {
+                    // create a bow
+                    let mut bow = GameObject::new(x, y, '}', "bow", colors::SEPIA, false);
+                    bow.item = Some(Item::Bow);
+                    bow
+                }
+                Some(Item::ThrowingKnife) => {
+                    // create a throwing knife
+                    let mut knife = GameObject::new(x, y, '(', "throwing knife", colors::SKY, false);
+                    knife.item = Some(Item::ThrowingKnife);
+                    knife
+                }
+                Some(Item::Sword) => {
+                    // create a sword
+                    let mut sword = GameObject::new(x, y, '/', "sword", colors::SKY, false);
+                    sword.item = Some(Item::Sword);
+                    sword.equipment = Some(Equipment {
+                        slot: Slot::RightHand,
+                        equipped: false,
+                        power_bonus: 3,
+                        defense_bonus: 0,
+                        max_hp_bonus: 0,
+                    });
+                    sword
+                }
+                Some(Item::Shield) => {
+                    // create a shield
+                    let mut shield = GameObject::new(x, y, '[', "shield", colors::DARKER_ORANGE, false);
+                    shield.item = Some(Item::Shield);
+                    shield.equipment = Some(Equipment {
+                        slot: Slot::LeftHand,
+                        equipped: false,
+                        power_bonus: 0,
+                        defense_bonus: 1,
+                        max_hp_bonus: 0,
+                    });
+                    shield
+                }
+                Some(Item::Helmet) => {
+                    // create a helmet
+                    let mut helmet = GameObject::new(x, y, '^', "helmet", colors::LIGHT_GREY, false);
+                    helmet.item = Some(Item::Helmet);
+                    helmet.equipment = Some(Equipment {
+                        slot: Slot::Head,
+                        equipped: false,
+                        power_bonus: 0,
+                        defense_bonus: 1,
+                        max_hp_bonus: 5,
+                    });
+                    helmet
+                }
+                Some(Item::Armor) => {
+                    // create a suit of armor
+                    let mut armor = GameObject::new(x, y, '&', "armor", colors::LIGHT_GREY, false);
+                    armor.item = Some(Item::Armor);
+                    armor.equipment = Some(Equipment {
+                        slot: Slot::Body,
+                        equipped: false,
+                        power_bonus: 0,
+                        defense_bonus: 2,
+                        max_hp_bonus: 10,
+                    });
+                    armor
+                }
+                None => continue,
             };
-            monster.alive = true;
-            game_objects.push(monster);
+            game_objects.push(item);
         }
     }
 }
 
-pub fn ai_take_turn(monster_index: usize, tcod: &Tcod, game: &mut Game, game_objects: &mut [GameObject]) {
+pub fn ai_take_turn(monster_index: usize, game: &mut Game, game_objects: &mut [GameObject]) {
+    use Ai::*;
+    // Take the AI out of the monster while it acts, then put back whatever it turns into.
+    if let Some(ai) = game_objects[monster_index].ai.take() {
+        let new_ai = match ai {
+            Basic => ai_basic(monster_index, game, game_objects),
+            Archer => ai_archer(monster_index, game, game_objects),
+            Confused { previous_ai, num_turns } => {
+                ai_confused(monster_index, game, game_objects, previous_ai, num_turns)
+            }
+        };
+        game_objects[monster_index].ai = Some(new_ai);
+    }
+}
+
+fn ai_basic(monster_index: usize, game: &mut Game, game_objects: &mut [GameObject]) -> Ai {
     // A basic monster takes its turn. If you can see it, it can see you!
     let (monster_x, monster_y) = game_objects[monster_index].get_position();
-    if tcod.fov.is_in_fov(monster_x, monster_y) {
-        if game_objects[monster_index].distance_to(&game_objects[PLAYER]) >= 2.0 {
+    if game.fov.is_in_fov(monster_x, monster_y) {
+        let hp = game_objects[monster_index].fighter.map_or(0, |fighter| fighter.hp);
+        let badly_hurt = hp * 100 < game_objects[monster_index].max_hp(game) * FLEE_HP_PERCENT;
+        if badly_hurt && flee_from_player(monster_index, game, game_objects) {
+            // ran for it; cornered, it fights on below
+        } else if game_objects[monster_index].distance_to(&game_objects[PLAYER]) >= 2.0 {
             // move toward player if far away.
             let (player_x, player_y) = game_objects[PLAYER].get_position();
             move_toward(monster_index, player_x, player_y, &game.map, game_objects);
@@ -174,6 +483,117 @@ pub fn ai_take_turn(monster_index: usize, tcod: &Tcod, game: &mut Game, game_obj
             monster.attack(player, game);
         }
     }
+    Ai::Basic
+}
+
+fn ai_archer(monster_index: usize, game: &mut Game, game_objects: &mut [GameObject]) -> Ai {
+    // An archer likes to stay a few steps away from the player, and shoots whenever it has a clear shot.
+    let (monster_x, monster_y) = game_objects[monster_index].get_position();
+    let player_alive = game_objects[PLAYER].fighter.is_some_and(|fighter| fighter.hp > 0);
+    if !game.fov.is_in_fov(monster_x, monster_y) || !player_alive {
+        return Ai::Archer;
+    }
+
+    let distance = game_objects[monster_index].distance_to(&game_objects[PLAYER]);
+    let backed_off = distance < ARCHER_KEEP_DISTANCE && step_away_from_player(monster_index, game, game_objects);
+    if backed_off {
+        // that was its turn
+    } else if distance < 2.0 {
+        // cornered: fight back up close
+        let (monster, player) = mut_two(monster_index, PLAYER, game_objects);
+        monster.attack(player, game);
+    } else if can_shoot(monster_index, PLAYER, ARROW_RANGE, &game.map, game_objects) {
+        let target = game_objects[PLAYER].get_position();
+        fire(monster_index, target, BOW_POWER, ARROW, game, game_objects);
+    } else {
+        let (player_x, player_y) = game_objects[PLAYER].get_position();
+        move_toward(monster_index, player_x, player_y, &game.map, game_objects);
+    }
+    Ai::Archer
+}
+
+// Step to the neighbouring tile furthest from the player, if any is further than where the monster is now.
+fn step_away_from_player(monster_index: usize, game: &Game, game_objects: &mut [GameObject]) -> bool {
+    let (x, y) = game_objects[monster_index].get_position();
+    let (player_x, player_y) = game_objects[PLAYER].get_position();
+    let distance_from_player = |x: i32, y: i32| (x - player_x).pow(2) + (y - player_y).pow(2);
+    let step = NEIGHBOURS
+        .iter()
+        .filter(|&&(dx, dy)| {
+            !is_blocked(x + dx, y + dy, &game.map, game_objects) && !squeezes_between_walls(x, y, dx, dy, &game.map)
+        })
+        .max_by_key(|&&(dx, dy)| distance_from_player(x + dx, y + dy))
+        .filter(|&&(dx, dy)| distance_from_player(x + dx, y + dy) > distance_from_player(x, y));
+    match step {
+        Some(&(dx, dy)) => {
+            move_by(monster_index, dx, dy, &game.map, game_objects);
+            true
+        }
+        None => false,
+    }
+}
+
+// Rebuild the maps for fleeing from where the player is now, once for all the monsters.
+// Tiles with a monster on them cost more to get through, so they spread out instead of queueing.
+fn update_flee_maps(game: &mut Game, game_objects: &[GameObject]) {
+    let mut crowded = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    for game_object in game_objects.iter().filter(|game_object| game_object.blocks_tile) {
+        crowded[game_object.x as usize][game_object.y as usize] = true;
+    }
+    let (player_x, player_y) = game_objects[PLAYER].get_position();
+    let flee_maps = game.flee_maps.get_or_insert_with(FleeMaps::new);
+    flee_maps.player.build_weighted(&game.map, &[(player_x, player_y, 0)], |x, y| {
+        if crowded[x as usize][y as usize] { CROWDED_TILE_COST } else { 1 }
+    });
+    flee_maps.flee.build_flee(&game.map, &flee_maps.player);
+}
+
+// Walk downhill on a map for fleeing from the player, towards open space rather than into a dead end.
+// Returns false if there's nowhere better to go.
+fn flee_from_player(monster_index: usize, game: &Game, game_objects: &mut [GameObject]) -> bool {
+    let flee_map = match game.flee_maps {
+        Some(ref flee_maps) => &flee_maps.flee,
+        None => return false,
+    };
+    let (x, y) = game_objects[monster_index].get_position();
+    match flee_map.lowest_neighbour(&game.map, x, y) {
+        Some((next_x, next_y)) if !is_blocked(next_x, next_y, &game.map, game_objects) => {
+            move_by(monster_index, next_x - x, next_y - y, &game.map, game_objects);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn ai_confused(
+    monster_index: usize,
+    game: &mut Game,
+    game_objects: &mut [GameObject],
+    previous_ai: Box<Ai>,
+    num_turns: i32,
+) -> Ai {
+    if num_turns > 0 {
+        // still confused ...
+        // move in a random direction, and decrease the number of turns confused
+        move_by(
+            monster_index,
+            game.rng.gen_range(-1, 2),
+            game.rng.gen_range(-1, 2),
+            &game.map,
+            game_objects,
+        );
+        Ai::Confused {
+            previous_ai,
+            num_turns: num_turns - 1,
+        }
+    } else {
+        // restore the previous AI (this one will be deleted)
+        game.messages.add(
+            format!("The {} is no longer confused!", game_objects[monster_index].name),
+            colors::RED,
+        );
+        *previous_ai
+    }
 }
 
 // Need this to allow monster to attack
@@ -190,3 +610,234 @@ pub fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use crate::colors::WHITE;
+    use crate::monsters::builtin_monsters;
+    use crate::player_move_or_attack;
+    use crate::tile::map_from;
+    use super::*;
+
+    fn generate(seed: u64) -> (Map, Vec<GameObject>) {
+        let mut game_objects = vec![GameObject::new(0, 0, '@', "Player", WHITE, false)];
+        let mut rng = GameRng::new(seed);
+        let map = make_map(&mut game_objects, &builtin_monsters(), 1, &mut rng);
+        (map, game_objects)
+    }
+
+    fn positions(game_objects: &[GameObject]) -> Vec<(String, i32, i32)> {
+        game_objects
+            .iter()
+            .map(|game_object| (game_object.name.clone(), game_object.x, game_object.y))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_dungeon() {
+        let (first_map, first_objects) = generate(1234);
+        let (second_map, second_objects) = generate(1234);
+        assert!(first_map == second_map);
+        assert_eq!(positions(&first_objects), positions(&second_objects));
+    }
+
+    #[test]
+    fn different_seeds_generate_different_dungeons() {
+        let (first_map, _) = generate(1234);
+        let (second_map, _) = generate(4321);
+        assert!(first_map != second_map);
+    }
+
+    // Play a number of turns with the given moves, no window involved.
+    fn play(seed: u64, moves: &[(i32, i32)]) -> (Game, Vec<GameObject>) {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), seed);
+        for &(dx, dy) in moves {
+            if !game_objects[PLAYER].alive {
+                break;
+            }
+            player_move_or_attack(dx, dy, &mut game, &mut game_objects);
+            end_player_turn(&mut game, &mut game_objects);
+
+            let (x, y) = game_objects[PLAYER].get_position();
+            assert!(!game.map[x as usize][y as usize].is_blocked);
+        }
+        (game, game_objects)
+    }
+
+    #[test]
+    fn turn_loop_runs_headless_and_replays_exactly() {
+        let mut rng = GameRng::new(7);
+        let moves: Vec<_> = (0..500)
+            .map(|_| (rng.gen_range(-1, 2), rng.gen_range(-1, 2)))
+            .collect();
+
+        let (first_game, first_objects) = play(99, &moves);
+        let (second_game, second_objects) = play(99, &moves);
+
+        assert!(first_game.map == second_game.map);
+        assert_eq!(positions(&first_objects), positions(&second_objects));
+        assert_eq!(first_objects[PLAYER].fighter, second_objects[PLAYER].fighter);
+        let explored = first_game.map.iter().flatten().filter(|tile| tile.is_explored).count();
+        assert!(explored > 0);
+    }
+
+    #[test]
+    fn badly_hurt_monsters_run_away() {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        game.map = vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        game_objects.truncate(1);
+        game_objects[PLAYER].set_position(10, 10);
+        let mut orc = builtin_monsters()[0].spawn(11, 10);
+        orc.fighter.as_mut().unwrap().hp = 2;
+        game_objects.push(orc);
+
+        for _ in 0..3 {
+            end_player_turn(&mut game, &mut game_objects);
+        }
+        assert!(game_objects[1].distance_to(&game_objects[PLAYER]) > 3.0);
+        assert_eq!(game.messages.count_starting_with("Orc attacks"), 0);
+    }
+
+    #[test]
+    fn monster_next_to_the_player_attacks() {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        // an open room with just the player and an orc
+        game.map = vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        game_objects.truncate(1);
+        game_objects[PLAYER].set_position(10, 10);
+        game_objects.push(builtin_monsters()[0].spawn(11, 10));
+
+        end_player_turn(&mut game, &mut game_objects);
+
+        assert_eq!(game.messages.count_starting_with("Orc attacks Player"), 1);
+    }
+
+    #[test]
+    fn confusion_lasts_exactly_as_many_turns_as_it_says() {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        game.map = vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        game_objects.truncate(1);
+        let mut orc = builtin_monsters()[0].spawn(20, 20);
+        orc.ai = Some(Ai::Confused { previous_ai: Box::new(Ai::Basic), num_turns: 3 });
+        game_objects.push(orc);
+
+        // three turns stumbling around ...
+        for _ in 0..3 {
+            ai_take_turn(1, &mut game, &mut game_objects);
+            assert!(matches!(game_objects[1].ai, Some(Ai::Confused { .. })));
+        }
+        assert_eq!(game.messages.count_starting_with("The Orc is no longer confused"), 0);
+
+        // ... and back to normal on the next
+        ai_take_turn(1, &mut game, &mut game_objects);
+        assert!(matches!(game_objects[1].ai, Some(Ai::Basic)));
+        assert_eq!(game.messages.count_starting_with("The Orc is no longer confused"), 1);
+    }
+
+    #[test]
+    fn fast_monsters_act_more_often_than_slow_ones() {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        game.map = vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        game_objects.truncate(1);
+        game_objects[PLAYER].set_position(10, 10);
+        let template = |name: &str| builtin_monsters().into_iter().find(|template| template.name == name).unwrap();
+        // a bat bites twice a turn, a zombie every other turn, starting with the second
+        game_objects.push(template("Bat").spawn(11, 10));
+        let mut zombie = template("Zombie");
+        // no stunning, the player is standing still anyway
+        zombie.on_hit = None;
+        game_objects.push(zombie.spawn(9, 10));
+
+        let mut attacks = vec![];
+        for _ in 0..4 {
+            end_player_turn(&mut game, &mut game_objects);
+            attacks.push((
+                game.messages.count_starting_with("Bat attacks"),
+                game.messages.count_starting_with("Zombie attacks"),
+            ));
+        }
+        assert_eq!(attacks, vec![(2, 0), (4, 1), (6, 1), (8, 2)]);
+    }
+
+    #[test]
+    fn archers_back_off_then_shoot() {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        game.map = vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        game_objects.truncate(1);
+        game_objects[PLAYER].set_position(10, 10);
+        let archer = builtin_monsters().into_iter().find(|template| template.ai == Ai::Archer).unwrap();
+        game_objects.push(archer.spawn(11, 10));
+
+        end_player_turn(&mut game, &mut game_objects);
+        assert!(game_objects[1].distance_to(&game_objects[PLAYER]) > 1.5, "it stepped back instead of attacking");
+        assert_eq!(game.messages.count_starting_with("Kobold archer attacks"), 0);
+
+        for _ in 0..3 {
+            end_player_turn(&mut game, &mut game_objects);
+        }
+        assert!(game_objects[1].distance_to(&game_objects[PLAYER]) >= ARCHER_KEEP_DISTANCE);
+        assert!(game.messages.count_starting_with("Kobold archer shoots Player") > 0);
+        assert!(!game.projectiles.is_empty());
+    }
+
+    #[test]
+    fn archers_shoot_as_hard_as_a_bow_not_as_they_hit() {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        game.map = vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        game_objects.truncate(1);
+        game_objects[PLAYER].set_position(10, 10);
+        game_objects[PLAYER].fighter.as_mut().unwrap().hp = 10_000;
+        let archer = builtin_monsters().into_iter().find(|template| template.ai == Ai::Archer).unwrap();
+        let mut archer = archer.spawn(15, 10);
+        archer.fighter.as_mut().unwrap().base_power = 100;
+        game_objects.push(archer);
+
+        for _ in 0..20 {
+            end_player_turn(&mut game, &mut game_objects);
+        }
+        // even a critical hit with a bow can't do more than this
+        let most = (BOW_POWER + BOW_POWER / 2) * 2;
+        let damage: Vec<i32> = game
+            .messages
+            .iter()
+            .filter_map(|(text, _)| {
+                let rest = text.strip_prefix("Kobold archer shoots Player for ")?;
+                rest.split(' ').next()?.parse().ok()
+            })
+            .collect();
+        assert!(!damage.is_empty(), "it never hit");
+        assert!(damage.iter().all(|&damage| damage <= most), "{:?}", damage);
+    }
+
+    #[test]
+    fn player_moves_diagonally_but_not_between_walls() {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        game.map = map_from(&[
+            "######",
+            "#....#",
+            "#.#..#",
+            "##...#",
+            "######",
+        ]);
+        game_objects.truncate(1);
+        game_objects[PLAYER].set_position(1, 2);
+
+        // the walls at (2, 2) and (1, 3) touch at the corners, so there's no way through
+        player_move_or_attack(1, 1, &mut game, &mut game_objects);
+        assert_eq!(game_objects[PLAYER].get_position(), (1, 2));
+
+        player_move_or_attack(1, -1, &mut game, &mut game_objects);
+        assert_eq!(game_objects[PLAYER].get_position(), (2, 1));
+        player_move_or_attack(1, 1, &mut game, &mut game_objects);
+        assert_eq!(game_objects[PLAYER].get_position(), (3, 2));
+    }
+
+    #[test]
+    fn waiting_in_place_does_nothing() {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        let position = game_objects[PLAYER].get_position();
+        player_move_or_attack(0, 0, &mut game, &mut game_objects);
+        assert_eq!(game_objects[PLAYER].get_position(), position);
+        assert_eq!(game_objects[PLAYER].fighter.map(|fighter| fighter.hp), Some(30));
+    }
+}
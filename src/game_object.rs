@@ -1,13 +1,22 @@
-use tcod::colors::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::colors::*;
+#[cfg(feature = "tui")]
 use tcod::console::*;
 use crate::PLAYER;
 use crate::Map;
+use crate::combat::roll_attack;
+use crate::effects::{add_effect, Effect, StatusEffect, HASTE_SPEED_PERCENT};
 use crate::fighter::*;
 use crate::game::*;
+use crate::messages::Messages;
+use crate::pathfinding::{find_path, MAX_PATH_LENGTH};
+use crate::scheduler::Actor;
+use crate::item::{Equipment, Item};
 
 // This is a generic object: the player, a monster, an item, the stairs...
 // It's always represented by a character on screen.
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GameObject {
     pub x: i32,
     pub y: i32,
@@ -16,8 +25,17 @@ pub struct GameObject {
     pub color: Color,
     pub blocks_tile: bool,
     pub alive: bool,
+    pub level: i32,
+    // Shown on explored tiles even when outside the player's FOV (e.g. the stairs).
+    pub always_visible: bool,
     pub fighter: Option<Fighter>,
     pub ai: Option<Ai>,
+    pub item: Option<Item>,
+    pub equipment: Option<Equipment>,
+    // Saved up towards the next action, see the scheduler.
+    pub energy: i32,
+    // Poison and the like, with how long each has left.
+    pub effects: Vec<StatusEffect>,
 }
 
 impl GameObject {
@@ -30,12 +48,19 @@ impl GameObject {
             color,
             blocks_tile,
             alive: false,
+            level: 1,
+            always_visible: false,
             fighter: None,
             ai: None,
+            item: None,
+            equipment: None,
+            energy: 0,
+            effects: vec![],
         }
     }
     // The dyn keyword in &mut dyn Console highlights that Console is a trait
     // and not a concrete type (such as a struct or enum).
+    #[cfg(feature = "tui")]
     pub fn draw(&self, console: &mut dyn Console) {
         console.set_default_foreground(self.color);
         console.put_char(self.x, self.y, self.char, BackgroundFlag::None)
@@ -56,7 +81,13 @@ impl GameObject {
         ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
     }
 
-    pub fn take_damage(&mut self, damage: i32, game: &mut Game) {
+    // distance to some coordinates
+    pub fn distance(&self, x: i32, y: i32) -> f32 {
+        (((x - self.x).pow(2) + (y - self.y).pow(2)) as f32).sqrt()
+    }
+
+    // Returns the experience the victim was worth if this damage killed it.
+    pub fn take_damage(&mut self, damage: i32, game: &mut Game) -> Option<i32> {
         if let Some(fighter) = self.fighter.as_mut() {
             if damage > 0 {
                 fighter.hp -= damage
@@ -66,37 +97,197 @@ impl GameObject {
             if fighter.hp <= 0 {
                 self.alive = false;
                 fighter.on_death.callback(self, game);
+                return Some(fighter.xp);
             }
         }
+        None
     }
 
-    pub fn attack(&mut self, target: &mut GameObject, game: &mut Game) {
-        let my_power = self.fighter.map_or(0, |fighter| fighter.power);
-        let target_defense = target.fighter.map_or(0, |fighter| fighter.defense);
-        let damage = my_power - target_defense;
-        if damage > 0 {
-            game.messages.add(
-                format!(
-                    "{} attacks {} for {} hit points.",
-                    self.name, target.name, damage
-                ),
-                YELLOW);
-            target.take_damage(damage, game);
+    pub fn is_stunned(&self) -> bool {
+        self.effects.iter().any(|status_effect| status_effect.effect == Effect::Stun)
+    }
+
+    // How fast it is compared to usual, in percent.
+    pub fn speed_percent(&self) -> i32 {
+        if self.effects.iter().any(|status_effect| status_effect.effect == Effect::Haste) {
+            HASTE_SPEED_PERCENT
+        } else {
+            100
+        }
+    }
+
+    // heal by the given amount, without going over the maximum
+    pub fn heal(&mut self, amount: i32, game: &Game) {
+        let max_hp = self.max_hp(game);
+        if let Some(ref mut fighter) = self.fighter {
+            fighter.hp += amount;
+            if fighter.hp > max_hp {
+                fighter.hp = max_hp;
+            }
+        }
+    }
+
+    // Only the player can wear equipment, and it's whatever is equipped in the inventory.
+    pub fn get_all_equipped(&self, game: &Game) -> Vec<Equipment> {
+        if self.fighter.is_some_and(|fighter| fighter.on_death == DeathCallback::Player) {
+            game.inventory
+                .iter()
+                .filter(|item| item.equipment.is_some_and(|equipment| equipment.equipped))
+                .map(|item| item.equipment.unwrap())
+                .collect()
+        } else {
+            vec![] // other objects have no equipment
+        }
+    }
+
+    pub fn power(&self, game: &Game) -> i32 {
+        let base_power = self.fighter.map_or(0, |fighter| fighter.base_power);
+        let bonus: i32 = self
+            .get_all_equipped(game)
+            .iter()
+            .map(|equipment| equipment.power_bonus)
+            .sum();
+        base_power + bonus
+    }
+
+    pub fn defense(&self, game: &Game) -> i32 {
+        let base_defense = self.fighter.map_or(0, |fighter| fighter.base_defense);
+        let bonus: i32 = self
+            .get_all_equipped(game)
+            .iter()
+            .map(|equipment| equipment.defense_bonus)
+            .sum();
+        base_defense + bonus
+    }
+
+    pub fn max_hp(&self, game: &Game) -> i32 {
+        let base_max_hp = self.fighter.map_or(0, |fighter| fighter.base_max_hp);
+        let bonus: i32 = self
+            .get_all_equipped(game)
+            .iter()
+            .map(|equipment| equipment.max_hp_bonus)
+            .sum();
+        base_max_hp + bonus
+    }
+
+    // Equip an item, if it's equipment. Only items in the player's inventory should be equipped.
+    pub fn equip(&mut self, messages: &mut Messages) {
+        if self.item.is_none() {
+            messages.add(
+                format!("Can't equip {:?} because it's not an Item.", self),
+                RED,
+            );
+            return;
+        };
+        if let Some(ref mut equipment) = self.equipment {
+            if !equipment.equipped {
+                equipment.equipped = true;
+                messages.add(
+                    format!("Equipped {} on {}.", self.name, equipment.slot),
+                    LIGHT_GREEN,
+                );
+            }
         } else {
-            game.messages.add(
-                format!(
-                    "{} attacks {} but the attack is deflected by {}'s armor.",
-                    self.name, target.name, target.name
-                ),
-                YELLOW);
+            messages.add(
+                format!("Can't equip {:?} because it's not an Equipment.", self),
+                RED,
+            );
         }
     }
+
+    pub fn dequip(&mut self, messages: &mut Messages) {
+        if self.item.is_none() {
+            messages.add(
+                format!("Can't dequip {:?} because it's not an Item.", self),
+                RED,
+            );
+            return;
+        };
+        if let Some(ref mut equipment) = self.equipment {
+            if equipment.equipped {
+                equipment.equipped = false;
+                messages.add(
+                    format!("Dequipped {} from {}.", self.name, equipment.slot),
+                    LIGHT_YELLOW,
+                );
+            }
+        } else {
+            messages.add(
+                format!("Can't dequip {:?} because it's not an Equipment.", self),
+                RED,
+            );
+        }
+    }
+
+    pub fn attack(&mut self, target: &mut GameObject, game: &mut Game) {
+        let power = self.power(game);
+        self.hit(target, power, "attacks", game);
+    }
+
+    // A ranged attack: the power comes from the bow or whatever was thrown, not from melee equipment.
+    pub fn shoot(&mut self, target: &mut GameObject, power: i32, game: &mut Game) {
+        self.hit(target, power, "shoots", game);
+    }
+
+    fn hit(&mut self, target: &mut GameObject, power: i32, verb: &str, game: &mut Game) {
+        let accuracy = self.fighter.map_or(0, |fighter| fighter.accuracy);
+        let evasion = target.fighter.map_or(0, |fighter| fighter.evasion);
+        let defense = target.defense(game);
+        let roll = roll_attack(power, accuracy, defense, evasion, &mut game.rng);
+        // the rolls go in the log too, so it's clear why an attack went the way it did
+        let to_hit = format!("rolled {} under {}", roll.to_hit, roll.hit_chance);
+        if !roll.is_hit() {
+            game.messages.add(format!("{} {} {} but misses. ({})", self.name, verb, target.name, to_hit), YELLOW);
+            return;
+        }
+
+        let critical = if roll.critical { " A critical hit!" } else { "" };
+        game.messages.add(
+            format!(
+                "{} {} {} for {} hit points.{} ({}, {} damage - {} armor)",
+                self.name, verb, target.name, roll.damage, critical, to_hit, roll.rolled_damage, defense
+            ),
+            if roll.critical { ORANGE } else { YELLOW });
+        if let Some(xp) = target.take_damage(roll.damage, game) {
+            // yield experience to the attacker
+            self.gain_xp(xp);
+        } else if let Some(on_hit) = self.fighter.and_then(|fighter| fighter.on_hit) {
+            // some attacks leave something behind, like poison
+            if game.rng.gen_range(0, 100) < on_hit.chance {
+                let from_player = self.fighter.is_some_and(|fighter| fighter.on_death == DeathCallback::Player);
+                let status_effect = StatusEffect { effect: on_hit.effect, turns: on_hit.turns, from_player };
+                add_effect(target, status_effect, game);
+            }
+        }
+    }
+
+    // Experience for a kill, whether by a blow or by something it left behind.
+    pub fn gain_xp(&mut self, xp: i32) {
+        if let Some(fighter) = self.fighter.as_mut() {
+            fighter.xp += xp;
+        }
+    }
+}
+
+// Only fighters take turns; items, corpses and the stairs have no speed and just wait.
+impl Actor for GameObject {
+    fn speed(&self) -> i32 {
+        self.fighter.map_or(0, |fighter| fighter.base_speed) * self.speed_percent() / 100
+    }
+
+    fn energy(&self) -> i32 {
+        self.energy
+    }
+
+    fn set_energy(&mut self, energy: i32) {
+        self.energy = energy;
+    }
 }
 
 // move by the given amount, if the destination is not blocked.
 pub fn move_by(index: usize, dx: i32, dy: i32, map: &Map, game_objects: &mut [GameObject]) {
     let (x, y) = game_objects[index].get_position();
-    let can_move = !is_blocked(x + dx, y + dy, map, game_objects);
+    let can_move = !is_blocked(x + dx, y + dy, map, game_objects) && !squeezes_between_walls(x, y, dx, dy, map);
     if can_move {
         game_objects[index].set_position(x + dx, y + dy);
     }
@@ -113,8 +304,10 @@ pub fn player_move_or_attack(dx: i32, dy: i32, game: &mut Game, game_objects: &m
     // Notice: It’s possible no match will be found, so it actually returns Option<usize> here.
     let target_index = game_objects
         .iter()
-        .position(|game_object|
-            game_object.fighter.is_some()
+        .enumerate()
+        .position(|(index, game_object)|
+            index != PLAYER
+                && game_object.fighter.is_some()
                 && game_object.get_position() == (x, y));
 
     // attack if target found, else try to move
@@ -130,16 +323,56 @@ pub fn player_move_or_attack(dx: i32, dy: i32, game: &mut Game, game_objects: &m
 }
 
 pub fn move_toward(index: usize, target_x: i32, target_y: i32, map: &Map, game_objects: &mut [GameObject]) {
-    // vector from this object to the target, and distance.
-    let dx = target_x - game_objects[index].x;
-    let dy = target_y - game_objects[index].y;
-    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
-
-    // normalize it to length 1 (preserving direction), then round it and
-    // convert to integer so the movement is restricted to the map grid.
-    let dx = (dx as f32 / distance).round() as i32;
-    let dy = (dy as f32 / distance).round() as i32;
-    move_by(index, dx, dy, map, game_objects);
+    let (x, y) = game_objects[index].get_position();
+    let (dx, dy) = step_toward((x, y), (target_x, target_y));
+    if direct_route_is_clear((x, y), (target_x, target_y), map, game_objects) {
+        move_by(index, dx, dy, map, game_objects);
+        return;
+    }
+
+    // something is in the way, so find a path around it (or just head that way if it's too far)
+    match find_path(map, game_objects, (x, y), (target_x, target_y), MAX_PATH_LENGTH) {
+        Some(path) => {
+            let (next_x, next_y) = path[0];
+            move_by(index, next_x - x, next_y - y, map, game_objects);
+        }
+        None => move_by(index, dx, dy, map, game_objects),
+    }
+}
+
+fn step_toward(from: (i32, i32), to: (i32, i32)) -> (i32, i32) {
+    // A diagonal step costs the same as a straight one, so close in on both axes at once
+    // for as long as the target is off in both directions.
+    ((to.0 - from.0).signum(), (to.1 - from.1).signum())
+}
+
+// Whether walking straight at the target gets there: the first step has to be free,
+// and no wall may be in the way after that.
+fn direct_route_is_clear(from: (i32, i32), to: (i32, i32), map: &Map, game_objects: &[GameObject]) -> bool {
+    let (dx, dy) = step_toward(from, to);
+    let mut position = (from.0 + dx, from.1 + dy);
+    if is_blocked(position.0, position.1, map, game_objects) {
+        return false;
+    }
+    if squeezes_between_walls(from.0, from.1, dx, dy, map) {
+        return false;
+    }
+    while position != to {
+        let (dx, dy) = step_toward(position, to);
+        if squeezes_between_walls(position.0, position.1, dx, dy, map) {
+            return false;
+        }
+        position = (position.0 + dx, position.1 + dy);
+        if map[position.0 as usize][position.1 as usize].is_blocked {
+            return false;
+        }
+    }
+    true
+}
+
+// Nothing can step diagonally between two walls that touch at the corners.
+pub fn squeezes_between_walls(x: i32, y: i32, dx: i32, dy: i32, map: &Map) -> bool {
+    dx != 0 && dy != 0 && map[(x + dx) as usize][y as usize].is_blocked && map[x as usize][(y + dy) as usize].is_blocked
 }
 
 // really is_blocked_or_occupied
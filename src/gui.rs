@@ -1,8 +1,11 @@
 use tcod::{BackgroundFlag, Color, Console, TextAlignment};
 use tcod::colors::*;
-use tcod::console::Offscreen;
-
-
+use tcod::console::{blit, Offscreen, Root};
+use tcod::input::{KeyCode, Mouse};
+use crate::GameObject;
+use crate::tui::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::fov::Fov;
+use crate::messages::Messages;
 
 pub fn render_bar(
     panel: &mut Offscreen,
@@ -34,4 +37,211 @@ pub fn render_bar(
         TextAlignment::Center,
         &format!("{}: {}/{}", name, value, maximum),
     )
-}
\ No newline at end of file
+}
+
+// Show a window with a header and a list of lettered options, and wait for the player to pick one.
+// Returns the index of the chosen option, or None if any other key was pressed.
+pub fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, root: &mut Root) -> Option<usize> {
+    assert!(
+        options.len() <= 26,
+        "Cannot have a menu with more than 26 options."
+    );
+
+    // calculate total height for the header (after auto-wrap) and one line per option
+    let header_height = if header.is_empty() {
+        0
+    } else {
+        root.get_height_rect(0, 0, width, SCREEN_HEIGHT, header)
+    };
+    let height = options.len() as i32 + header_height;
+
+    // create an off-screen console that represents the menu's window
+    let mut window = Offscreen::new(width, height);
+
+    // print the header, with auto-wrap
+    window.set_default_foreground(WHITE);
+    window.print_rect_ex(
+        0,
+        0,
+        width,
+        height,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        header,
+    );
+
+    // print all the options, each prefixed with its letter: (a), (b), ...
+    for (index, option_text) in options.iter().enumerate() {
+        let menu_letter = (b'a' + index as u8) as char;
+        let text = format!("({}) {}", menu_letter, option_text.as_ref());
+        window.print_ex(
+            0,
+            header_height + index as i32,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            text,
+        );
+    }
+
+    // blit the contents of "window" to the root console, centered on the screen.
+    // The background is drawn at 70% opacity so the map behind it stays visible.
+    let x = SCREEN_WIDTH / 2 - width / 2;
+    let y = SCREEN_HEIGHT / 2 - height / 2;
+    blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
+
+    // present the root console to the player and wait for a key-press
+    root.flush();
+    let key = root.wait_for_keypress(true);
+
+    // convert the ASCII code to an index; if it corresponds to an option, return it
+    if key.printable.is_alphabetic() {
+        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
+        if index < options.len() {
+            Some(index)
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+// A menu with no options, used to show a message to the player.
+pub fn msgbox(text: &str, width: i32, root: &mut Root) {
+    let options: &[&str] = &[];
+    menu(text, options, width, root);
+}
+
+// Show a menu with each item of the inventory as an option.
+pub fn inventory_menu(inventory: &[GameObject], header: &str, width: i32, root: &mut Root) -> Option<usize> {
+    let options = if inventory.is_empty() {
+        vec!["Inventory is empty.".into()]
+    } else {
+        inventory
+            .iter()
+            .map(|item| {
+                // show additional information, in case it's equipped
+                match item.equipment {
+                    Some(equipment) if equipment.equipped => {
+                        format!("{} (on {})", item.name, equipment.slot)
+                    }
+                    _ => item.name.clone(),
+                }
+            })
+            .collect()
+    };
+
+    let inventory_index = menu(header, &options, width, root);
+
+    // if an item was chosen, return it
+    if !inventory.is_empty() {
+        inventory_index
+    } else {
+        None
+    }
+}
+
+// Return a string with the names of all objects under the mouse that the player can see.
+pub fn get_names_under_mouse(mouse: Mouse, game_objects: &[GameObject], fov: &Fov) -> String {
+    let (x, y) = (mouse.cx as i32, mouse.cy as i32);
+
+    // create a list with the names of all objects at the mouse's coordinates and in FOV
+    let names = game_objects
+        .iter()
+        .filter(|game_object| game_object.get_position() == (x, y) && fov.is_in_fov(game_object.x, game_object.y))
+        .map(|game_object| game_object.name.clone())
+        .collect::<Vec<_>>();
+
+    names.join(", ") // join the names, separated by commas
+}
+
+// Show the whole message history, newest at the bottom. The arrow keys and page up/down scroll,
+// Home and End jump to either end, and any other key closes it.
+pub fn message_log(messages: &Messages, root: &mut Root) {
+    let width = SCREEN_WIDTH - 2;
+    let page_height = SCREEN_HEIGHT - 4;
+    let lines: Vec<(String, Color)> = messages
+        .iter()
+        .flat_map(|(text, color)| wrap(&text, width as usize).into_iter().map(move |line| (line, color)))
+        .collect();
+    let max_scroll = lines.len().saturating_sub(page_height as usize);
+    // how many lines up from the newest message the view is
+    let mut scroll = 0;
+
+    loop {
+        root.set_default_background(BLACK);
+        root.clear();
+        root.set_default_foreground(LIGHT_YELLOW);
+        root.print(1, 0, "Message log");
+        root.set_default_foreground(LIGHT_GREY);
+        root.print(1, SCREEN_HEIGHT - 1, "Up/Down, PageUp/PageDown, Home/End to scroll, any other key to close");
+
+        let end = lines.len() - scroll;
+        let start = end.saturating_sub(page_height as usize);
+        for (row, (line, color)) in lines[start..end].iter().enumerate() {
+            root.set_default_foreground(*color);
+            root.print(1, 2 + row as i32, line);
+        }
+        root.flush();
+
+        let page = page_height as usize;
+        match root.wait_for_keypress(true).code {
+            KeyCode::Up => scroll = (scroll + 1).min(max_scroll),
+            KeyCode::Down => scroll = scroll.saturating_sub(1),
+            KeyCode::PageUp => scroll = (scroll + page).min(max_scroll),
+            KeyCode::PageDown => scroll = scroll.saturating_sub(page),
+            KeyCode::Home => scroll = max_scroll,
+            KeyCode::End => scroll = 0,
+            // pressing Shift, Ctrl or Alt on its own isn't "any other key"
+            KeyCode::Shift | KeyCode::Control | KeyCode::Alt => {}
+            _ => break,
+        }
+    }
+}
+
+// Break text into lines of at most the given width, between words where possible.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+        // a single word that's too long for a line gets cut up
+        while line.chars().count() > width {
+            let rest = line.chars().skip(width).collect();
+            line = line.chars().take(width).collect();
+            lines.push(std::mem::replace(&mut line, rest));
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_that_fits_exactly_stays_on_one_line() {
+        assert_eq!(wrap("The Orc dies", 12), vec!["The Orc dies"]);
+        assert_eq!(wrap("The Orc dies", 11), vec!["The Orc", "dies"]);
+    }
+
+    #[test]
+    fn words_longer_than_a_line_are_cut_up() {
+        assert_eq!(wrap("a Fireballfireball!", 8), vec!["a", "Fireball", "fireball", "!"]);
+    }
+
+    #[test]
+    fn empty_text_is_one_empty_line() {
+        assert_eq!(wrap("", 10), vec![""]);
+        assert_eq!(wrap("   ", 10), vec![""]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::colors::*;
+use crate::{Game, GameObject, PLAYER};
+
+// The player can't carry more than this many items at once.
+pub const INVENTORY_SIZE: usize = 26;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    Heal,
+    Regeneration,
+    Haste,
+    Lightning,
+    Fireball,
+    Confuse,
+    Bow,
+    ThrowingKnife,
+    Sword,
+    Shield,
+    Helmet,
+    Armor,
+}
+
+// An object that can be equipped, yielding bonuses.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    pub equipped: bool,
+    pub power_bonus: i32,
+    pub defense_bonus: i32,
+    pub max_hp_bonus: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    LeftHand,
+    RightHand,
+    Head,
+    Body,
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Slot::LeftHand => write!(f, "left hand"),
+            Slot::RightHand => write!(f, "right hand"),
+            Slot::Head => write!(f, "head"),
+            Slot::Body => write!(f, "body"),
+        }
+    }
+}
+
+// add to the player's inventory and remove from the map
+pub fn pick_item_up(object_id: usize, game: &mut Game, game_objects: &mut Vec<GameObject>) {
+    if game.inventory.len() >= INVENTORY_SIZE {
+        game.messages.add(
+            format!(
+                "Your inventory is full, cannot pick up {}.",
+                game_objects[object_id].name
+            ),
+            RED,
+        );
+    } else {
+        let item = game_objects.swap_remove(object_id);
+        game.messages.add(format!("You picked up a {}!", item.name), GREEN);
+        game.inventory.push(item);
+    }
+}
+
+// remove from the player's inventory and put it back on the map at the player's feet
+pub fn drop_item(inventory_id: usize, game: &mut Game, game_objects: &mut Vec<GameObject>) {
+    let mut item = game.inventory.remove(inventory_id);
+    if item.equipment.is_some() {
+        item.dequip(&mut game.messages);
+    }
+    item.set_position(game_objects[PLAYER].x, game_objects[PLAYER].y);
+    game.messages.add(format!("You dropped a {}.", item.name), YELLOW);
+    game_objects.push(item);
+    clamp_player_hp(game, game_objects);
+}
+
+// Taking off something that gave extra hit points can leave the player with more than their new maximum.
+pub fn clamp_player_hp(game: &Game, game_objects: &mut [GameObject]) {
+    let max_hp = game_objects[PLAYER].max_hp(game);
+    if let Some(fighter) = game_objects[PLAYER].fighter.as_mut() {
+        fighter.hp = fighter.hp.min(max_hp);
+    }
+}
+
+// The inventory index of whatever is equipped in the given slot, if anything.
+pub fn get_equipped_in_slot(slot: Slot, inventory: &[GameObject]) -> Option<usize> {
+    for (inventory_id, item) in inventory.iter().enumerate() {
+        if item
+            .equipment
+            .is_some_and(|equipment| equipment.equipped && equipment.slot == slot)
+        {
+            return Some(inventory_id);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::create_game;
+    use crate::monsters::builtin_monsters;
+    use super::*;
+
+    #[test]
+    fn dropping_extra_hit_points_takes_them_away() {
+        let (mut game, mut game_objects) = create_game(&builtin_monsters(), 1);
+        let mut armor = GameObject::new(0, 0, '&', "armor", LIGHT_GREY, false);
+        armor.item = Some(Item::Armor);
+        armor.equipment = Some(Equipment {
+            slot: Slot::Body,
+            equipped: false,
+            power_bonus: 0,
+            defense_bonus: 2,
+            max_hp_bonus: 10,
+        });
+        armor.equip(&mut game.messages);
+        game.inventory.push(armor);
+        let base_max_hp = game_objects[PLAYER].fighter.unwrap().base_max_hp;
+
+        // healed all the way up while wearing it ...
+        let max_hp = game_objects[PLAYER].max_hp(&game);
+        assert_eq!(max_hp, base_max_hp + 10);
+        game_objects[PLAYER].fighter.as_mut().unwrap().hp = max_hp;
+
+        // ... then back down to the new maximum once it's off
+        let armor_id = game.inventory.len() - 1;
+        drop_item(armor_id, &mut game, &mut game_objects);
+        assert_eq!(game_objects[PLAYER].fighter.unwrap().hp, base_max_hp);
+    }
+}
@@ -0,0 +1,271 @@
+// Using items from the inventory. Many of them ask the player to pick a target first,
+// so this only exists with the window.
+use crate::colors::*;
+use crate::{Ai, ARROW, Game, GameObject, PLAYER};
+use crate::effects::{add_effect, Effect, StatusEffect};
+use crate::item::{clamp_player_hp, get_equipped_in_slot, Item};
+use crate::ranged::{fire, ARROW_RANGE, BOW_POWER};
+use crate::tui::{target_monster, target_ranged, target_tile, Tcod};
+
+const HEAL_AMOUNT: i32 = 4;
+const REGENERATION_AMOUNT: i32 = 1;
+const REGENERATION_NUM_TURNS: i32 = 20;
+// counted in the player's own turns, which come twice as fast while hasted
+const HASTE_NUM_TURNS: i32 = 20;
+const LIGHTNING_DAMAGE: i32 = 40;
+const LIGHTNING_RANGE: i32 = 5;
+const CONFUSE_RANGE: f32 = 8.0;
+const CONFUSE_NUM_TURNS: i32 = 10;
+const FIREBALL_RANGE: f32 = 8.0;
+const FIREBALL_RADIUS: i32 = 3;
+const FIREBALL_DAMAGE: i32 = 12;
+// Thrown things don't go as far as arrows, or hit as hard.
+const THROW_RANGE: i32 = 5;
+const THROWN_KNIFE_POWER: i32 = 4;
+const THROWN_KNIFE: (char, Color) = ('(', SKY);
+
+// What happened when an item was used, so the caller knows whether to remove it from the inventory.
+pub enum UseResult {
+    UsedUp,
+    UsedAndKept,
+    // it left the inventory and landed on the map at this tile
+    Thrown((i32, i32)),
+    Cancelled,
+}
+
+// Returns whether the item was actually used.
+pub fn use_item(inventory_id: usize, tcod: &mut Tcod, game: &mut Game, game_objects: &mut Vec<GameObject>) -> bool {
+    use Item::*;
+    // just call the "use_function" if it is defined
+    if let Some(item) = game.inventory[inventory_id].item {
+        let on_use = match item {
+            Heal => cast_heal,
+            Regeneration => cast_regeneration,
+            Haste => cast_haste,
+            Lightning => cast_lightning,
+            Fireball => cast_fireball,
+            Confuse => cast_confuse,
+            Bow => shoot_bow,
+            ThrowingKnife => throw_knife,
+            Sword | Shield | Helmet | Armor => toggle_equipment,
+        };
+        match on_use(inventory_id, tcod, game, game_objects) {
+            UseResult::UsedUp => {
+                // destroy after use, unless it was cancelled for some reason
+                game.inventory.remove(inventory_id);
+            }
+            UseResult::UsedAndKept => {} // do nothing
+            UseResult::Thrown((x, y)) => {
+                // it can be picked up again where it landed
+                let mut item = game.inventory.remove(inventory_id);
+                item.set_position(x, y);
+                game_objects.push(item);
+            }
+            UseResult::Cancelled => {
+                game.messages.add("Cancelled", WHITE);
+                return false;
+            }
+        }
+        true
+    } else {
+        game.messages.add(
+            format!("The {} cannot be used.", game.inventory[inventory_id].name),
+            WHITE,
+        );
+        false
+    }
+}
+
+fn cast_heal(_inventory_id: usize, _tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) -> UseResult {
+    // heal the player
+    let max_hp = game_objects[PLAYER].max_hp(game);
+    if let Some(fighter) = game_objects[PLAYER].fighter {
+        if fighter.hp == max_hp {
+            game.messages.add("You are already at full health.", RED);
+            return UseResult::Cancelled;
+        }
+        game.messages.add("Your wounds start to feel better!", LIGHT_VIOLET);
+        game_objects[PLAYER].heal(HEAL_AMOUNT, game);
+        return UseResult::UsedUp;
+    }
+    UseResult::Cancelled
+}
+
+fn cast_regeneration(_inventory_id: usize, _tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) -> UseResult {
+    // heal the player a little every turn for a while
+    game.messages.add("You feel your body knitting itself back together.", LIGHT_VIOLET);
+    let regeneration = StatusEffect {
+        effect: Effect::Regeneration(REGENERATION_AMOUNT),
+        turns: REGENERATION_NUM_TURNS,
+        from_player: true,
+    };
+    add_effect(&mut game_objects[PLAYER], regeneration, game);
+    UseResult::UsedUp
+}
+
+fn cast_haste(_inventory_id: usize, _tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) -> UseResult {
+    game.messages.add("Everything around you seems to slow down.", LIGHT_BLUE);
+    add_effect(&mut game_objects[PLAYER], StatusEffect { effect: Effect::Haste, turns: HASTE_NUM_TURNS, from_player: true }, game);
+    UseResult::UsedUp
+}
+
+// find the closest enemy, up to a maximum range, and in the player's FOV
+fn closest_monster(game: &Game, game_objects: &[GameObject], max_range: i32) -> Option<usize> {
+    let mut closest_enemy = None;
+    // start with (slightly more than) maximum range
+    let mut closest_distance = (max_range + 1) as f32;
+
+    for (id, game_object) in game_objects.iter().enumerate() {
+        if (id != PLAYER)
+            && game_object.fighter.is_some()
+            && game_object.ai.is_some()
+            && game.fov.is_in_fov(game_object.x, game_object.y)
+        {
+            // calculate distance between this object and the player
+            let distance = game_objects[PLAYER].distance_to(game_object);
+            if distance < closest_distance {
+                // it's closer, so remember it
+                closest_enemy = Some(id);
+                closest_distance = distance;
+            }
+        }
+    }
+    closest_enemy
+}
+
+fn cast_lightning(_inventory_id: usize, _tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) -> UseResult {
+    // find closest enemy (inside a maximum range) and damage it
+    let monster_id = closest_monster(game, game_objects, LIGHTNING_RANGE);
+    if let Some(monster_id) = monster_id {
+        // zap it!
+        game.messages.add(
+            format!(
+                "A lightning bolt strikes the {} with a loud thunder! \
+                 The damage is {} hit points.",
+                game_objects[monster_id].name, LIGHTNING_DAMAGE
+            ),
+            LIGHT_BLUE,
+        );
+        if let Some(xp) = game_objects[monster_id].take_damage(LIGHTNING_DAMAGE, game) {
+            if let Some(fighter) = game_objects[PLAYER].fighter.as_mut() {
+                fighter.xp += xp;
+            }
+        }
+        UseResult::UsedUp
+    } else {
+        // no enemy found within maximum range
+        game.messages.add("No enemy is close enough to strike.", RED);
+        UseResult::Cancelled
+    }
+}
+
+fn cast_fireball(_inventory_id: usize, tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) -> UseResult {
+    // ask the player for a target tile to throw a fireball at
+    game.messages.add(
+        "Left-click a target tile for the fireball, or right-click / Escape to cancel.",
+        LIGHT_CYAN,
+    );
+    let (x, y) = match target_tile(tcod, game, game_objects, Some(FIREBALL_RANGE)) {
+        Some(tile_position) => tile_position,
+        None => return UseResult::Cancelled,
+    };
+    game.messages.add(
+        format!(
+            "The fireball explodes, burning everything within {} tiles!",
+            FIREBALL_RADIUS
+        ),
+        ORANGE,
+    );
+
+    // everything in the blast radius gets burned, including the player
+    let mut xp_to_gain = 0;
+    for (id, game_object) in game_objects.iter_mut().enumerate() {
+        if game_object.distance(x, y) <= FIREBALL_RADIUS as f32 && game_object.fighter.is_some() {
+            game.messages.add(
+                format!(
+                    "The {} gets burned for {} hit points.",
+                    game_object.name, FIREBALL_DAMAGE
+                ),
+                ORANGE,
+            );
+            if let Some(xp) = game_object.take_damage(FIREBALL_DAMAGE, game) {
+                // don't reward the player for burning themself!
+                if id != PLAYER {
+                    xp_to_gain += xp;
+                }
+            }
+        }
+    }
+    if let Some(fighter) = game_objects[PLAYER].fighter.as_mut() {
+        fighter.xp += xp_to_gain;
+    }
+
+    UseResult::UsedUp
+}
+
+fn cast_confuse(_inventory_id: usize, tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) -> UseResult {
+    // ask the player for a target to confuse
+    game.messages.add(
+        "Left-click an enemy to confuse it, or right-click / Escape to cancel.",
+        LIGHT_CYAN,
+    );
+    let monster_id = target_monster(tcod, game, game_objects, Some(CONFUSE_RANGE));
+    if let Some(monster_id) = monster_id {
+        let old_ai = game_objects[monster_id].ai.take().unwrap_or(Ai::Basic);
+        // replace the monster's AI with a "confused" one; after
+        // some turns it will restore the old AI
+        game_objects[monster_id].ai = Some(Ai::Confused {
+            previous_ai: Box::new(old_ai),
+            num_turns: CONFUSE_NUM_TURNS,
+        });
+        game.messages.add(
+            format!(
+                "The eyes of {} look vacant, as it starts to stumble around!",
+                game_objects[monster_id].name
+            ),
+            LIGHT_GREEN,
+        );
+        UseResult::UsedUp
+    } else {
+        UseResult::Cancelled
+    }
+}
+
+fn shoot_bow(_inventory_id: usize, tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) -> UseResult {
+    // there's no running out of arrows
+    let target = match target_ranged(tcod, game, game_objects, ARROW_RANGE) {
+        Some(target) => target,
+        None => return UseResult::Cancelled,
+    };
+    fire(PLAYER, target, BOW_POWER, ARROW, game, game_objects);
+    UseResult::UsedAndKept
+}
+
+fn throw_knife(_inventory_id: usize, tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) -> UseResult {
+    let target = match target_ranged(tcod, game, game_objects, THROW_RANGE) {
+        Some(target) => target,
+        None => return UseResult::Cancelled,
+    };
+    let shot = fire(PLAYER, target, THROWN_KNIFE_POWER, THROWN_KNIFE, game, game_objects);
+    // it drops where it stopped, or at the player's feet if a wall was right in front of them
+    let landed = shot.path.last().copied().unwrap_or(game_objects[PLAYER].get_position());
+    UseResult::Thrown(landed)
+}
+
+fn toggle_equipment(inventory_id: usize, _tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) -> UseResult {
+    let equipment = match game.inventory[inventory_id].equipment {
+        Some(equipment) => equipment,
+        None => return UseResult::Cancelled,
+    };
+    if equipment.equipped {
+        game.inventory[inventory_id].dequip(&mut game.messages);
+    } else {
+        // if the slot is already being used, dequip whatever is there first
+        if let Some(current) = get_equipped_in_slot(equipment.slot, &game.inventory) {
+            game.inventory[current].dequip(&mut game.messages);
+        }
+        game.inventory[inventory_id].equip(&mut game.messages);
+    }
+    clamp_player_hp(game, game_objects);
+    UseResult::UsedAndKept
+}
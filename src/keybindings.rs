@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use serde::Deserialize;
+use tcod::input::{Key, KeyCode};
+
+const KEYBINDINGS_FILE: &str = "keybindings.json";
+
+// The names special keys go by in the keybindings file. Anything else is written as the character it types.
+const KEY_NAMES: [(&str, KeyCode); 27] = [
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Insert", KeyCode::Insert),
+    ("Delete", KeyCode::Delete),
+    ("Escape", KeyCode::Escape),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Space", KeyCode::Spacebar),
+    ("NumPad0", KeyCode::NumPad0),
+    ("NumPad1", KeyCode::NumPad1),
+    ("NumPad2", KeyCode::NumPad2),
+    ("NumPad3", KeyCode::NumPad3),
+    ("NumPad4", KeyCode::NumPad4),
+    ("NumPad5", KeyCode::NumPad5),
+    ("NumPad6", KeyCode::NumPad6),
+    ("NumPad7", KeyCode::NumPad7),
+    ("NumPad8", KeyCode::NumPad8),
+    ("NumPad9", KeyCode::NumPad9),
+    ("NumPadEnter", KeyCode::NumPadEnter),
+    ("F1", KeyCode::F1),
+];
+
+// Everything the player can do with a key press.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub enum Action {
+    MoveNorth,
+    MoveSouth,
+    MoveWest,
+    MoveEast,
+    MoveNorthWest,
+    MoveNorthEast,
+    MoveSouthWest,
+    MoveSouthEast,
+    Wait,
+    PickUp,
+    Inventory,
+    Drop,
+    Fire,
+    Descend,
+    Explore,
+    TravelToCorpse,
+    MessageLog,
+    Help,
+    Quit,
+}
+
+impl Action {
+    // in the order the help screen lists them
+    const ALL: [Action; 19] = [
+        Action::MoveNorth,
+        Action::MoveSouth,
+        Action::MoveWest,
+        Action::MoveEast,
+        Action::MoveNorthWest,
+        Action::MoveNorthEast,
+        Action::MoveSouthWest,
+        Action::MoveSouthEast,
+        Action::Wait,
+        Action::PickUp,
+        Action::Inventory,
+        Action::Drop,
+        Action::Fire,
+        Action::Descend,
+        Action::Explore,
+        Action::TravelToCorpse,
+        Action::MessageLog,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    // which way a movement action goes
+    pub fn direction(self) -> Option<(i32, i32)> {
+        use Action::*;
+        match self {
+            MoveNorth => Some((0, -1)),
+            MoveSouth => Some((0, 1)),
+            MoveWest => Some((-1, 0)),
+            MoveEast => Some((1, 0)),
+            MoveNorthWest => Some((-1, -1)),
+            MoveNorthEast => Some((1, -1)),
+            MoveSouthWest => Some((-1, 1)),
+            MoveSouthEast => Some((1, 1)),
+            _ => None,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        use Action::*;
+        match self {
+            MoveNorth => "Move north",
+            MoveSouth => "Move south",
+            MoveWest => "Move west",
+            MoveEast => "Move east",
+            MoveNorthWest => "Move north-west",
+            MoveNorthEast => "Move north-east",
+            MoveSouthWest => "Move south-west",
+            MoveSouthEast => "Move south-east",
+            Wait => "Wait a turn",
+            PickUp => "Pick up an item",
+            Inventory => "Use an item",
+            Drop => "Drop an item",
+            Fire => "Shoot or throw at a target",
+            Descend => "Go down the stairs",
+            Explore => "Explore",
+            TravelToCorpse => "Go back to the last kill",
+            MessageLog => "Show the message log",
+            Help => "Show this help",
+            Quit => "Save and quit",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        use Action::*;
+        match self {
+            MoveNorth => &["Up", "NumPad8", "k"],
+            MoveSouth => &["Down", "NumPad2", "j"],
+            MoveWest => &["Left", "NumPad4", "h"],
+            MoveEast => &["Right", "NumPad6", "l"],
+            MoveNorthWest => &["Home", "NumPad7", "y"],
+            MoveNorthEast => &["PageUp", "NumPad9", "u"],
+            MoveSouthWest => &["End", "NumPad1", "b"],
+            MoveSouthEast => &["PageDown", "NumPad3", "n"],
+            Wait => &["NumPad5", "."],
+            PickUp => &["g"],
+            Inventory => &["i"],
+            Drop => &["d"],
+            Fire => &["f"],
+            Descend => &["<"],
+            Explore => &["x"],
+            TravelToCorpse => &["c"],
+            MessageLog => &["m"],
+            Help => &["?", "F1"],
+            Quit => &["Escape"],
+        }
+    }
+}
+
+// One key, either a special key or the character a key types.
+#[derive(Clone, Debug, PartialEq)]
+enum Binding {
+    Code(KeyCode),
+    Text(String),
+}
+
+impl Binding {
+    fn parse(name: &str) -> Option<Binding> {
+        if let Some(&(_, code)) = KEY_NAMES.iter().find(|(key_name, _)| *key_name == name) {
+            Some(Binding::Code(code))
+        } else if name.chars().count() == 1 {
+            Some(Binding::Text(name.into()))
+        } else {
+            None
+        }
+    }
+
+    fn matches(&self, code: KeyCode, text: &str) -> bool {
+        match self {
+            Binding::Code(binding) => *binding == code,
+            Binding::Text(binding) => code == KeyCode::Text && binding == text,
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Code(code) => {
+                let name = KEY_NAMES
+                    .iter()
+                    .find(|(_, key_code)| key_code == code)
+                    .map_or("?", |(name, _)| name);
+                write!(f, "{}", name)
+            }
+            Binding::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+pub struct KeyBindings {
+    bindings: Vec<(Action, Vec<Binding>)>,
+}
+
+impl KeyBindings {
+    pub fn action_for(&self, key: Key) -> Option<Action> {
+        self.action_for_input(key.code, key.text())
+    }
+
+    fn action_for_input(&self, code: KeyCode, text: &str) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, bindings)| bindings.iter().any(|binding| binding.matches(code, text)))
+            .map(|&(action, _)| action)
+    }
+
+    // One line per action with the keys it's bound to, for the help screen.
+    pub fn describe(&self) -> Vec<String> {
+        self.bindings
+            .iter()
+            .map(|(action, bindings)| {
+                let keys: Vec<String> = bindings.iter().map(|binding| binding.to_string()).collect();
+                format!("{:<26}{}", action.description(), keys.join(", "))
+            })
+            .collect()
+    }
+
+    // Two actions on the same key would mean one of them could never be used.
+    fn check_conflicts(&self) -> Result<(), KeyBindingsError> {
+        for (index, (action, bindings)) in self.bindings.iter().enumerate() {
+            for binding in bindings {
+                let other = self.bindings[index + 1..]
+                    .iter()
+                    .find(|(_, other_bindings)| other_bindings.contains(binding));
+                if let Some(&(other_action, _)) = other {
+                    return Err(KeyBindingsError::Conflict {
+                        key: binding.to_string(),
+                        actions: (*action, other_action),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .iter()
+            .map(|&action| {
+                let keys = action.default_keys().iter().filter_map(|name| Binding::parse(name)).collect();
+                (action, keys)
+            })
+            .collect();
+        KeyBindings { bindings }
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyBindingsError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    UnknownKey { action: Action, key: String },
+    Conflict { key: String, actions: (Action, Action) },
+}
+
+impl fmt::Display for KeyBindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyBindingsError::Io(error) => write!(f, "{}: {}", KEYBINDINGS_FILE, error),
+            KeyBindingsError::Parse(error) => write!(f, "{}: {}", KEYBINDINGS_FILE, error),
+            KeyBindingsError::UnknownKey { action, key } => write!(
+                f,
+                "{}: {:?}: \"{}\" is not a key name or a single character",
+                KEYBINDINGS_FILE, action, key
+            ),
+            KeyBindingsError::Conflict { key, actions: (first, second) } => write!(
+                f,
+                "{}: \"{}\" is bound to both {:?} and {:?}",
+                KEYBINDINGS_FILE, key, first, second
+            ),
+        }
+    }
+}
+
+impl Error for KeyBindingsError {}
+
+// Load the keybindings file on top of the default bindings, or just use the defaults if there is no file.
+pub fn load_keybindings() -> Result<KeyBindings, KeyBindingsError> {
+    match fs::read_to_string(KEYBINDINGS_FILE) {
+        Ok(json) => parse_keybindings(&json),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(KeyBindings::default()),
+        Err(error) => Err(KeyBindingsError::Io(error)),
+    }
+}
+
+// Actions in the file get exactly the keys listed there; the rest keep their default keys.
+fn parse_keybindings(json: &str) -> Result<KeyBindings, KeyBindingsError> {
+    let overrides: BTreeMap<Action, Vec<String>> = serde_json::from_str(json).map_err(KeyBindingsError::Parse)?;
+
+    let mut key_bindings = KeyBindings::default();
+    for (action, keys) in overrides {
+        let bindings = keys
+            .iter()
+            .map(|key| {
+                Binding::parse(key).ok_or_else(|| KeyBindingsError::UnknownKey { action, key: key.clone() })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(entry) = key_bindings.bindings.iter_mut().find(|(entry_action, _)| *entry_action == action) {
+            entry.1 = bindings;
+        }
+    }
+    key_bindings.check_conflicts()?;
+    Ok(key_bindings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_have_no_conflicts() {
+        let key_bindings = KeyBindings::default();
+        assert!(key_bindings.check_conflicts().is_ok());
+        for action in Action::ALL.iter() {
+            for name in action.default_keys() {
+                assert!(Binding::parse(name).is_some(), "{:?} has an unknown default key {}", action, name);
+            }
+        }
+    }
+
+    #[test]
+    fn keys_map_to_actions() {
+        let key_bindings = KeyBindings::default();
+        assert_eq!(key_bindings.action_for_input(KeyCode::Up, ""), Some(Action::MoveNorth));
+        assert_eq!(key_bindings.action_for_input(KeyCode::NumPad3, ""), Some(Action::MoveSouthEast));
+        assert_eq!(key_bindings.action_for_input(KeyCode::Text, "y"), Some(Action::MoveNorthWest));
+        assert_eq!(key_bindings.action_for_input(KeyCode::Text, "<"), Some(Action::Descend));
+        assert_eq!(key_bindings.action_for_input(KeyCode::Text, "Q"), None);
+        assert_eq!(key_bindings.action_for_input(KeyCode::F2, ""), None);
+    }
+
+    #[test]
+    fn the_file_replaces_the_keys_of_the_actions_it_lists() {
+        let key_bindings = parse_keybindings(r#"{ "PickUp": [","], "Explore": ["o", "Tab"] }"#).unwrap();
+        assert_eq!(key_bindings.action_for_input(KeyCode::Text, ","), Some(Action::PickUp));
+        assert_eq!(key_bindings.action_for_input(KeyCode::Text, "g"), None);
+        assert_eq!(key_bindings.action_for_input(KeyCode::Tab, ""), Some(Action::Explore));
+        assert_eq!(key_bindings.action_for_input(KeyCode::Text, "k"), Some(Action::MoveNorth));
+    }
+
+    #[test]
+    fn conflicting_bindings_are_reported() {
+        let error = parse_keybindings(r#"{ "Explore": ["g"] }"#).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "keybindings.json: \"g\" is bound to both PickUp and Explore"
+        );
+    }
+
+    #[test]
+    fn unknown_keys_and_actions_are_reported() {
+        let error = parse_keybindings(r#"{ "Wait": ["NumPad55"] }"#).err().unwrap();
+        assert!(matches!(error, KeyBindingsError::UnknownKey { action: Action::Wait, .. }), "{}", error);
+        let error = parse_keybindings(r#"{ "Dance": ["z"] }"#).err().unwrap();
+        assert!(matches!(error, KeyBindingsError::Parse(_)), "{}", error);
+    }
+
+    #[test]
+    fn help_lists_every_action_with_its_keys() {
+        let lines = KeyBindings::default().describe();
+        assert_eq!(lines.len(), Action::ALL.len());
+        assert!(lines[0].starts_with("Move north"));
+        assert!(lines[0].ends_with("Up, NumPad8, k"));
+    }
+}
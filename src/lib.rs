@@ -0,0 +1,46 @@
+// Without the window (the "tui" feature) there's nobody to save the game, travel or pick things up,
+// only the arena, so the code for those goes unused there.
+#![cfg_attr(not(feature = "tui"), allow(dead_code))]
+
+pub mod game_object;
+mod tile;
+pub mod game;
+mod rect;
+pub mod fighter;
+#[cfg(feature = "tui")]
+mod gui;
+mod messages;
+mod item;
+#[cfg(feature = "tui")]
+mod item_use;
+mod spawn;
+pub mod monsters;
+mod rng;
+mod fov;
+mod pathfinding;
+mod ranged;
+pub mod combat;
+pub mod dijkstra;
+mod activity;
+mod effects;
+#[cfg(feature = "tui")]
+mod keybindings;
+pub mod scheduler;
+mod save;
+mod colors;
+// The window, and everything about playing in it. The arena builds without it.
+#[cfg(feature = "tui")]
+mod tui;
+
+use crate::fighter::*;
+use crate::game::*;
+use crate::game_object::*;
+
+// Game Map
+const MAP_WIDTH: i32 = 80;
+const MAP_HEIGHT: i32 = 43;
+
+pub const PLAYER: usize = 0;
+
+#[cfg(feature = "tui")]
+pub use crate::tui::run;
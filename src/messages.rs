@@ -1,12 +1,36 @@
-use tcod::colors::*;
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use crate::colors::*;
 
+// Only this many messages are kept; once there are more, the oldest ones are dropped.
+const MAX_MESSAGES: usize = 1000;
+
+#[derive(Serialize, Deserialize)]
 pub struct Messages {
-    messages: Vec<(String, Color)>,
+    messages: VecDeque<Message>,
+}
+
+// One line of the log. The same message several times in a row is kept once, with a count.
+#[derive(Serialize, Deserialize)]
+struct Message {
+    text: String,
+    color: Color,
+    count: u32,
+}
+
+impl Message {
+    fn text(&self) -> String {
+        if self.count > 1 {
+            format!("{} (x{})", self.text, self.count)
+        } else {
+            self.text.clone()
+        }
+    }
 }
 
 impl Messages {
     pub fn new() -> Self {
-        Self { messages: vec![] }
+        Self { messages: VecDeque::new() }
     }
 
     // The <T: Into<String>> bit makes the add function generic.
@@ -16,7 +40,17 @@ impl Messages {
     // This lets us pass both &str (and therefore string literals)
     // and String (an output of the format! macro among other things).
     pub fn add<T: Into<String>>(&mut self, message: T, color: Color) {
-        self.messages.push((message.into(), color))
+        let text = message.into();
+        if let Some(last) = self.messages.back_mut() {
+            if last.text == text && last.color == color {
+                last.count += 1;
+                return;
+            }
+        }
+        self.messages.push_back(Message { text, color, count: 1 });
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
     }
 
     // As we’re keeping the inner messages field private,
@@ -25,7 +59,49 @@ impl Messages {
     // "This function returns some type implementing this trait"
     // and let the compiler figure it out.
     // DoubleEndedIterator is a Trait.
-    pub fn iter(&self) -> impl DoubleEndedIterator<Item=&(String, Color)> {
-        self.messages.iter()
+    // Each message comes out the way it should be shown, with its count if it was repeated.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item=(String, Color)> + '_ {
+        self.messages.iter().map(|message| (message.text(), message.color))
+    }
+
+    // How many times a message starting like this was logged, repeats included.
+    #[cfg(test)]
+    pub fn count_starting_with(&self, start: &str) -> u32 {
+        self.messages.iter().filter(|message| message.text.starts_with(start)).map(|message| message.count).sum()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(messages: &Messages) -> Vec<String> {
+        messages.iter().map(|(text, _)| text).collect()
+    }
+
+    #[test]
+    fn repeated_messages_are_counted() {
+        let mut messages = Messages::new();
+        messages.add("The Orc attacks you.", RED);
+        messages.add("The Orc attacks you.", RED);
+        messages.add("The Orc attacks you.", RED);
+        messages.add("You attack the Orc.", WHITE);
+        messages.add("The Orc attacks you.", RED);
+        assert_eq!(
+            texts(&messages),
+            vec!["The Orc attacks you. (x3)", "You attack the Orc.", "The Orc attacks you."]
+        );
+    }
+
+    #[test]
+    fn oldest_messages_are_dropped_first() {
+        let mut messages = Messages::new();
+        for turn in 0..MAX_MESSAGES + 5 {
+            messages.add(format!("Turn {}", turn), WHITE);
+        }
+        let texts = texts(&messages);
+        assert_eq!(texts.len(), MAX_MESSAGES);
+        assert_eq!(texts[0], "Turn 5");
+        assert_eq!(texts[MAX_MESSAGES - 1], format!("Turn {}", MAX_MESSAGES + 4));
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use serde::Deserialize;
+use crate::colors::{self, Color};
+use crate::{Ai, DeathCallback, Fighter, GameObject};
+use crate::effects::{Effect, OnHit};
+use crate::scheduler::NORMAL_SPEED;
+use crate::spawn::Transition;
+
+const MONSTERS_FILE: &str = "monsters.json";
+
+// Everything needed to spawn one kind of monster.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub char: char,
+    pub color: Color,
+    pub max_hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    // how often it acts: twice as often at twice the normal speed
+    #[serde(default = "normal_speed")]
+    pub speed: i32,
+    // how much more or less often it hits, and gets hit, than usual
+    #[serde(default)]
+    pub accuracy: i32,
+    #[serde(default)]
+    pub evasion: i32,
+    pub ai: Ai,
+    #[serde(default)]
+    pub on_hit: Option<OnHit>,
+    pub xp: i32,
+    // spawn weight from each dungeon level onwards, sorted by level
+    pub spawn_weights: Vec<Transition>,
+}
+
+impl MonsterTemplate {
+    pub fn spawn(&self, x: i32, y: i32) -> GameObject {
+        let mut monster = GameObject::new(x, y, self.char, &self.name, self.color, true);
+        monster.fighter = Some(Fighter {
+            base_max_hp: self.max_hp,
+            hp: self.max_hp,
+            base_defense: self.defense,
+            base_power: self.power,
+            base_speed: self.speed,
+            accuracy: self.accuracy,
+            evasion: self.evasion,
+            xp: self.xp,
+            on_death: DeathCallback::Monster,
+            on_hit: self.on_hit,
+        });
+        monster.ai = Some(self.ai.clone());
+        monster.alive = true;
+        monster
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        if self.max_hp <= 0 {
+            return Err(format!("max_hp must be positive, got {}", self.max_hp));
+        }
+        if self.defense < 0 {
+            return Err(format!("defense must not be negative, got {}", self.defense));
+        }
+        if self.power < 0 {
+            return Err(format!("power must not be negative, got {}", self.power));
+        }
+        if self.speed <= 0 {
+            return Err(format!("speed must be positive, got {}", self.speed));
+        }
+        if self.xp < 0 {
+            return Err(format!("xp must not be negative, got {}", self.xp));
+        }
+        if let Some(on_hit) = self.on_hit {
+            on_hit.validate()?;
+        }
+        if let Ai::Confused { .. } = self.ai {
+            return Err("ai cannot start out Confused".into());
+        }
+        if self.spawn_weights.is_empty() {
+            return Err("spawn_weights must have at least one entry".into());
+        }
+        let mut previous_level = 0;
+        for transition in &self.spawn_weights {
+            if transition.level <= previous_level {
+                return Err(format!(
+                    "spawn_weights levels must start at 1 and go up, got level {} after level {}",
+                    transition.level, previous_level
+                ));
+            }
+            previous_level = transition.level;
+        }
+        Ok(())
+    }
+}
+
+fn normal_speed() -> i32 {
+    NORMAL_SPEED
+}
+
+#[derive(Debug)]
+pub enum MonsterFileError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    InvalidEntry { index: usize, name: Option<String>, reason: String },
+}
+
+impl fmt::Display for MonsterFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MonsterFileError::Io(error) => write!(f, "{}: {}", MONSTERS_FILE, error),
+            MonsterFileError::Parse(error) => write!(f, "{}: {}", MONSTERS_FILE, error),
+            MonsterFileError::InvalidEntry { index, name: Some(name), reason } => write!(
+                f,
+                "{}: monster #{} (\"{}\"): {}",
+                MONSTERS_FILE, index, name, reason
+            ),
+            MonsterFileError::InvalidEntry { index, name: None, reason } => {
+                write!(f, "{}: monster #{}: {}", MONSTERS_FILE, index, reason)
+            }
+        }
+    }
+}
+
+impl Error for MonsterFileError {}
+
+// Load the monster templates from the monsters file, or use the built-in ones if there is no file.
+pub fn load_monsters() -> Result<Vec<MonsterTemplate>, MonsterFileError> {
+    match fs::read_to_string(MONSTERS_FILE) {
+        Ok(json) => parse_monsters(&json),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(builtin_monsters()),
+        Err(error) => Err(MonsterFileError::Io(error)),
+    }
+}
+
+fn parse_monsters(json: &str) -> Result<Vec<MonsterTemplate>, MonsterFileError> {
+    // Parse each entry on its own, so an error can say which monster it's about.
+    let entries: Vec<serde_json::Value> = serde_json::from_str(json).map_err(MonsterFileError::Parse)?;
+
+    let mut monsters = Vec::with_capacity(entries.len());
+    let mut names = HashSet::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let name = entry.get("name").and_then(|name| name.as_str()).map(String::from);
+        let invalid = |reason: String| MonsterFileError::InvalidEntry {
+            index,
+            name: name.clone(),
+            reason,
+        };
+
+        let monster: MonsterTemplate = serde_json::from_value(entry).map_err(|error| invalid(error.to_string()))?;
+        monster.validate().map_err(invalid)?;
+        if !names.insert(monster.name.clone()) {
+            return Err(invalid("another monster already has this name".into()));
+        }
+        monsters.push(monster);
+    }
+
+    if monsters.is_empty() {
+        return Err(MonsterFileError::InvalidEntry {
+            index: 0,
+            name: None,
+            reason: "the file must define at least one monster".into(),
+        });
+    }
+    Ok(monsters)
+}
+
+pub fn builtin_monsters() -> Vec<MonsterTemplate> {
+    vec![
+        MonsterTemplate {
+            name: "Orc".into(),
+            char: 'o',
+            color: colors::DESATURATED_GREEN,
+            max_hp: 10,
+            defense: 0,
+            power: 3,
+            speed: NORMAL_SPEED,
+            accuracy: 0,
+            evasion: 0,
+            ai: Ai::Basic,
+            on_hit: None,
+            xp: 35,
+            spawn_weights: vec![Transition { level: 1, value: 80 }],
+        },
+        MonsterTemplate {
+            name: "Troll".into(),
+            char: 'T',
+            color: colors::DARKER_GREEN,
+            max_hp: 16,
+            defense: 1,
+            power: 4,
+            speed: NORMAL_SPEED,
+            accuracy: 0,
+            evasion: -2,
+            ai: Ai::Basic,
+            on_hit: None,
+            xp: 100,
+            spawn_weights: vec![
+                Transition { level: 1, value: 5 },
+                Transition { level: 3, value: 15 },
+                Transition { level: 5, value: 30 },
+                Transition { level: 7, value: 60 },
+            ],
+        },
+        // flits about, getting two bites in for every step the player takes, and is hard to hit
+        MonsterTemplate {
+            name: "Bat".into(),
+            char: 'b',
+            color: colors::SEPIA,
+            max_hp: 4,
+            defense: 0,
+            power: 3,
+            speed: 2 * NORMAL_SPEED,
+            accuracy: 0,
+            evasion: 4,
+            ai: Ai::Basic,
+            on_hit: None,
+            xp: 15,
+            spawn_weights: vec![Transition { level: 2, value: 20 }],
+        },
+        // hits hard enough to stun, but is clumsy and only gets to act every other turn
+        MonsterTemplate {
+            name: "Zombie".into(),
+            char: 'Z',
+            color: colors::DARK_SEPIA,
+            max_hp: 20,
+            defense: 0,
+            power: 6,
+            speed: NORMAL_SPEED / 2,
+            accuracy: -2,
+            evasion: -2,
+            ai: Ai::Basic,
+            on_hit: Some(OnHit { effect: Effect::Stun, turns: 1, chance: 25 }),
+            xp: 60,
+            spawn_weights: vec![
+                Transition { level: 2, value: 10 },
+                Transition { level: 4, value: 20 },
+            ],
+        },
+        // its bite is poisonous
+        MonsterTemplate {
+            name: "Giant spider".into(),
+            char: 's',
+            color: colors::DARK_GREY,
+            max_hp: 8,
+            defense: 1,
+            power: 3,
+            speed: NORMAL_SPEED,
+            accuracy: 1,
+            evasion: 1,
+            ai: Ai::Basic,
+            on_hit: Some(OnHit { effect: Effect::Poison(1), turns: 5, chance: 50 }),
+            xp: 40,
+            spawn_weights: vec![Transition { level: 3, value: 15 }],
+        },
+        // stays back and shoots arrows, with a good eye
+        MonsterTemplate {
+            name: "Kobold archer".into(),
+            char: 'k',
+            color: colors::AMBER,
+            max_hp: 6,
+            defense: 0,
+            power: 4,
+            speed: NORMAL_SPEED,
+            accuracy: 2,
+            evasion: 0,
+            ai: Ai::Archer,
+            on_hit: None,
+            xp: 30,
+            spawn_weights: vec![Transition { level: 2, value: 15 }],
+        },
+    ]
+}
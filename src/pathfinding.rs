@@ -0,0 +1,223 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use crate::game::Map;
+use crate::{squeezes_between_walls, GameObject};
+
+// Paths longer than this aren't searched for, so a monster far away doesn't cost much.
+pub const MAX_PATH_LENGTH: i32 = 40;
+// A tile with a blocking object (usually another monster) on it can still be part of a path,
+// since it will probably have moved on by then, but it costs as much as this many steps.
+const BLOCKING_OBJECT_COST: i32 = 10;
+
+// the eight tiles around a tile, straight ones first
+pub const NEIGHBOURS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, 0),
+    (0, 1),
+    (-1, 0),
+    (1, -1),
+    (1, 1),
+    (-1, 1),
+    (-1, -1),
+];
+
+// Find the cheapest path from one tile to another with A*, moving in all 8 directions.
+// The path doesn't include the start but does include the goal, so path[0] is the next step.
+// Returns None if there is no path of at most max_length steps.
+pub fn find_path(
+    map: &Map,
+    game_objects: &[GameObject],
+    from: (i32, i32),
+    to: (i32, i32),
+    max_length: i32,
+) -> Option<Vec<(i32, i32)>> {
+    let width = map.len() as i32;
+    let height = map.first().map_or(0, |column| column.len()) as i32;
+    let in_map = |(x, y): (i32, i32)| x >= 0 && y >= 0 && x < width && y < height;
+    if from == to || !in_map(from) || !in_map(to) || map[to.0 as usize][to.1 as usize].is_blocked {
+        return None;
+    }
+    let index = |(x, y): (i32, i32)| (x * height + y) as usize;
+
+    // where the blocking objects are, apart from the ones we're coming from and going to
+    let mut occupied = vec![false; (width * height) as usize];
+    for game_object in game_objects.iter().filter(|game_object| game_object.blocks_tile) {
+        let position = game_object.get_position();
+        if in_map(position) && position != from && position != to {
+            occupied[index(position)] = true;
+        }
+    }
+
+    // Diagonal steps cost the same as straight ones, so the number of steps left is at least
+    // the larger of the two distances.
+    let estimate = |(x, y): (i32, i32)| (to.0 - x).abs().max((to.1 - y).abs());
+
+    let mut cost = vec![i32::MAX; (width * height) as usize];
+    let mut steps = vec![0; (width * height) as usize];
+    let mut came_from = vec![None; (width * height) as usize];
+    // always look at the most promising tile next; ties are broken on position so the result is repeatable
+    let mut open = BinaryHeap::new();
+    cost[index(from)] = 0;
+    open.push(Reverse((estimate(from), from)));
+
+    while let Some(Reverse((_, position))) = open.pop() {
+        if position == to {
+            // walk back to the start to get the path
+            let mut path = vec![to];
+            let mut current = to;
+            while let Some(previous) = came_from[index(current)] {
+                if previous == from {
+                    break;
+                }
+                path.push(previous);
+                current = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if steps[index(position)] >= max_length {
+            continue;
+        }
+
+        for (dx, dy) in NEIGHBOURS.iter() {
+            let next = (position.0 + dx, position.1 + dy);
+            if !in_map(next)
+                || map[next.0 as usize][next.1 as usize].is_blocked
+                || squeezes_between_walls(position.0, position.1, *dx, *dy, map)
+            {
+                continue;
+            }
+            let step_cost = if occupied[index(next)] { BLOCKING_OBJECT_COST } else { 1 };
+            let next_cost = cost[index(position)] + step_cost;
+            if next_cost < cost[index(next)] {
+                cost[index(next)] = next_cost;
+                steps[index(next)] = steps[index(position)] + 1;
+                came_from[index(next)] = Some(position);
+                open.push(Reverse((next_cost + estimate(next), next)));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colors::WHITE;
+    use crate::tile::map_from;
+    use super::*;
+
+    const MAZE: [&str; 9] = [
+        "###########",
+        "#...#.....#",
+        "#.#.#.###.#",
+        "#.#...#...#",
+        "#.#####.###",
+        "#.#.....#.#",
+        "#.#.###.#.#",
+        "#...#.....#",
+        "###########",
+    ];
+
+    fn monster(x: i32, y: i32) -> GameObject {
+        GameObject::new(x, y, 'o', "orc", WHITE, true)
+    }
+
+    // every step has to be next to the one before it and on a floor tile
+    fn assert_walkable(map: &Map, from: (i32, i32), path: &[(i32, i32)]) {
+        let mut previous = from;
+        for &(x, y) in path {
+            assert!((x - previous.0).abs() <= 1 && (y - previous.1).abs() <= 1, "{:?} to {:?}", previous, (x, y));
+            assert!(!map[x as usize][y as usize].is_blocked, "{:?} is a wall", (x, y));
+            previous = (x, y);
+        }
+    }
+
+    #[test]
+    fn straight_line_in_the_open() {
+        let map = map_from(&["#######", "#.....#", "#######"]);
+        let path = find_path(&map, &[], (1, 1), (5, 1), MAX_PATH_LENGTH).unwrap();
+        assert_eq!(path, vec![(2, 1), (3, 1), (4, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn finds_the_way_through_a_maze() {
+        let map = map_from(&MAZE);
+        let path = find_path(&map, &[], (1, 1), (9, 7), MAX_PATH_LENGTH).unwrap();
+        assert_walkable(&map, (1, 1), &path);
+        assert_eq!(path.last(), Some(&(9, 7)));
+        // down the left side and through the lower corridor is shorter than going over the top
+        assert_eq!(path.len(), 13);
+        assert!(path.contains(&(1, 6)));
+    }
+
+    #[test]
+    fn no_path_into_a_closed_room() {
+        let map = map_from(&[
+            "#########",
+            "#...#...#",
+            "#...#...#",
+            "#...#...#",
+            "#########",
+        ]);
+        assert!(find_path(&map, &[], (1, 2), (6, 2), MAX_PATH_LENGTH).is_none());
+    }
+
+    #[test]
+    fn no_squeezing_between_diagonal_walls() {
+        let map = map_from(&[
+            "#####",
+            "#.#.#",
+            "##..#",
+            "#####",
+        ]);
+        assert!(find_path(&map, &[], (1, 1), (2, 2), MAX_PATH_LENGTH).is_none());
+        assert_eq!(find_path(&map, &[], (3, 1), (2, 2), MAX_PATH_LENGTH), Some(vec![(2, 2)]));
+    }
+
+    #[test]
+    fn paths_longer_than_the_cap_are_not_searched() {
+        let map = map_from(&MAZE);
+        assert!(find_path(&map, &[], (1, 1), (9, 7), 12).is_none());
+        assert!(find_path(&map, &[], (1, 1), (9, 7), 13).is_some());
+    }
+
+    #[test]
+    fn goes_around_monsters_when_it_can() {
+        let map = map_from(&[
+            "#######",
+            "#.....#",
+            "#.....#",
+            "#.....#",
+            "#######",
+        ]);
+        let path = find_path(&map, &[monster(3, 2)], (1, 2), (5, 2), MAX_PATH_LENGTH).unwrap();
+        assert_eq!(path.len(), 4);
+        assert!(!path.contains(&(3, 2)));
+    }
+
+    #[test]
+    fn goes_through_monsters_when_it_must() {
+        let map = map_from(&["#######", "#.....#", "#######"]);
+        let path = find_path(&map, &[monster(3, 1)], (1, 1), (5, 1), MAX_PATH_LENGTH).unwrap();
+        assert_eq!(path, vec![(2, 1), (3, 1), (4, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn monster_walks_around_a_wall_corner() {
+        let map = map_from(&[
+            "#######",
+            "#.....#",
+            "#.###.#",
+            "#.#...#",
+            "#.#...#",
+            "#######",
+        ]);
+        // the player is just the other side of a wall from the orc
+        let mut game_objects = vec![monster(1, 4), monster(4, 3)];
+        game_objects[0].name = "player".into();
+        for _ in 0..10 {
+            crate::move_toward(1, 1, 4, &map, &mut game_objects);
+        }
+        assert!(game_objects[1].distance_to(&game_objects[0]) < 2.0);
+    }
+}
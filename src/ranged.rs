@@ -0,0 +1,189 @@
+use crate::colors::Color;
+use crate::{Game, GameObject, Map, mut_two, squeezes_between_walls};
+
+// How far bows and archers can shoot.
+pub const ARROW_RANGE: i32 = 8;
+// How hard an arrow hits, whoever shoots it: a strong archer doesn't make a stronger bow.
+pub const BOW_POWER: i32 = 5;
+
+// Something flying across the map, for the GUI to show before the next frame.
+pub struct Projectile {
+    pub char: char,
+    pub color: Color,
+    pub path: Vec<(i32, i32)>,
+}
+
+// Where a projectile went, and what it hit if it hit anything.
+#[derive(Debug, PartialEq)]
+pub struct Shot {
+    pub path: Vec<(i32, i32)>,
+    pub hit: Option<usize>,
+}
+
+// The tiles on a straight line between two points (Bresenham's line algorithm),
+// leaving out the start and ending with the end.
+pub fn line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let (step_x, step_y) = ((to.0 - x).signum(), (to.1 - y).signum());
+    let mut error = dx + dy;
+
+    let mut points = vec![];
+    while (x, y) != to {
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+        points.push((x, y));
+    }
+    points
+}
+
+// Follow the line from one point towards another. The projectile stops short of the first wall in the way,
+// or on the first creature (a monster, or the player) or other blocking object, which is what it hits.
+pub fn line_of_fire(map: &Map, game_objects: &[GameObject], from: (i32, i32), to: (i32, i32)) -> Shot {
+    let mut path = vec![];
+    let mut previous = from;
+    for (x, y) in line(from, to) {
+        let in_map = x >= 0 && y >= 0 && (x as usize) < map.len() && (y as usize) < map[0].len();
+        if !in_map
+            || map[x as usize][y as usize].is_blocked
+            || squeezes_between_walls(previous.0, previous.1, x - previous.0, y - previous.1, map)
+        {
+            break;
+        }
+        path.push((x, y));
+        let hit = game_objects
+            .iter()
+            .position(|game_object| {
+                (game_object.blocks_tile || game_object.fighter.is_some()) && game_object.get_position() == (x, y)
+            });
+        if hit.is_some() {
+            return Shot { path, hit };
+        }
+        previous = (x, y);
+    }
+    Shot { path, hit: None }
+}
+
+// Whether the shooter is in range of the target and nothing stands between them.
+pub fn can_shoot(shooter: usize, target: usize, range: i32, map: &Map, game_objects: &[GameObject]) -> bool {
+    game_objects[shooter].distance_to(&game_objects[target]) <= range as f32
+        && line_of_fire(map, game_objects, game_objects[shooter].get_position(), game_objects[target].get_position()).hit
+            == Some(target)
+}
+
+// Fire a projectile from the shooter towards a tile. Whatever it hits first takes the given power
+// against its defense, just like a melee attack.
+pub fn fire(
+    shooter: usize,
+    target: (i32, i32),
+    power: i32,
+    (char, color): (char, Color),
+    game: &mut Game,
+    game_objects: &mut [GameObject],
+) -> Shot {
+    let shot = line_of_fire(&game.map, game_objects, game_objects[shooter].get_position(), target);
+    game.projectiles.push(Projectile { char, color, path: shot.path.clone() });
+    if let Some(hit) = shot.hit {
+        if game_objects[hit].fighter.is_some() {
+            let (shooter, hit) = mut_two(shooter, hit, game_objects);
+            shooter.shoot(hit, power, game);
+        }
+    }
+    shot
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::monsters::builtin_monsters;
+    use crate::tile::map_from;
+    use super::*;
+
+    const ROOM: [&str; 7] = [
+        "###########",
+        "#.........#",
+        "#.........#",
+        "#....#....#",
+        "#.........#",
+        "#.........#",
+        "###########",
+    ];
+
+    fn monster(x: i32, y: i32) -> GameObject {
+        builtin_monsters()[0].spawn(x, y)
+    }
+
+    #[test]
+    fn lines_go_straight_and_diagonal() {
+        assert_eq!(line((1, 1), (4, 1)), vec![(2, 1), (3, 1), (4, 1)]);
+        assert_eq!(line((4, 4), (4, 2)), vec![(4, 3), (4, 2)]);
+        assert_eq!(line((1, 1), (3, 3)), vec![(2, 2), (3, 3)]);
+        assert_eq!(line((0, 0), (4, 2)), vec![(1, 1), (2, 1), (3, 2), (4, 2)]);
+        assert_eq!(line((2, 2), (2, 2)), vec![]);
+    }
+
+    #[test]
+    fn lines_are_the_same_length_both_ways() {
+        for &(from, to) in &[((0, 0), (7, 3)), ((5, 1), (-2, 4)), ((3, 3), (3, -6))] {
+            let there = line(from, to);
+            let back = line(to, from);
+            assert_eq!(there.len(), back.len());
+            assert_eq!(there.last(), Some(&to));
+            assert_eq!(back.last(), Some(&from));
+        }
+    }
+
+    #[test]
+    fn projectiles_reach_an_empty_tile() {
+        let map = map_from(&ROOM);
+        let shot = line_of_fire(&map, &[monster(1, 1)], (1, 1), (8, 1));
+        assert_eq!(shot.path.last(), Some(&(8, 1)));
+        assert_eq!(shot.hit, None);
+    }
+
+    #[test]
+    fn projectiles_stop_at_walls() {
+        let map = map_from(&ROOM);
+        let shot = line_of_fire(&map, &[], (2, 3), (8, 3));
+        assert_eq!(shot.path, vec![(3, 3), (4, 3)]);
+        assert_eq!(shot.hit, None);
+    }
+
+    #[test]
+    fn projectiles_hit_the_first_blocking_object() {
+        let map = map_from(&ROOM);
+        let mut corpse = monster(3, 1);
+        corpse.blocks_tile = false;
+        corpse.fighter = None;
+        let game_objects = [monster(1, 1), corpse, monster(5, 1), monster(7, 1)];
+        let shot = line_of_fire(&map, &game_objects, (1, 1), (7, 1));
+        // straight over the corpse, into the first monster
+        assert_eq!(shot.path, vec![(2, 1), (3, 1), (4, 1), (5, 1)]);
+        assert_eq!(shot.hit, Some(2));
+    }
+
+    #[test]
+    fn shooting_needs_range_and_a_clear_line() {
+        let map = map_from(&ROOM);
+        let game_objects = [monster(1, 1), monster(9, 1), monster(3, 3), monster(8, 3)];
+        assert!(can_shoot(0, 1, 8, &map, &game_objects));
+        assert!(!can_shoot(0, 1, 7, &map, &game_objects), "out of range");
+        assert!(!can_shoot(2, 3, 8, &map, &game_objects), "a wall is in the way");
+        assert!(!can_shoot(1, 0, 8, &map, &[monster(1, 1), monster(9, 1), monster(5, 1)]), "someone is in the way");
+    }
+
+    #[test]
+    fn the_player_can_be_hit_without_blocking_the_tile() {
+        let map = map_from(&ROOM);
+        let mut player = monster(1, 1);
+        player.blocks_tile = false;
+        assert!(can_shoot(1, 0, 8, &map, &[player, monster(6, 1)]));
+    }
+}
@@ -0,0 +1,157 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use serde::{Deserialize, Serialize};
+use crate::game::Game;
+use crate::game_object::GameObject;
+use crate::{MAP_HEIGHT, MAP_WIDTH, PLAYER};
+
+const SAVE_FILE: &str = "savegame";
+// Bump this whenever the layout of anything that gets saved changes,
+// so old save files are rejected with a clear message instead of a confusing parse error.
+const SAVE_VERSION: u32 = 13;
+
+#[derive(Serialize)]
+struct SaveDataRef<'a> {
+    version: u32,
+    game: &'a Game,
+    game_objects: &'a [GameObject],
+}
+
+#[derive(Deserialize)]
+struct SaveData {
+    game: Game,
+    game_objects: Vec<GameObject>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Corrupt(serde_json::Error),
+    VersionMismatch { found: u64, expected: u32 },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "could not access save file: {}", error),
+            SaveError::Corrupt(error) => write!(f, "save file is corrupt: {}", error),
+            SaveError::VersionMismatch { found, expected } => write!(
+                f,
+                "save file is from an incompatible version (found version {}, expected {})",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(error: serde_json::Error) -> Self {
+        SaveError::Corrupt(error)
+    }
+}
+
+pub fn save_game(game: &Game, game_objects: &[GameObject]) -> Result<(), SaveError> {
+    let save_data = SaveDataRef { version: SAVE_VERSION, game, game_objects };
+    let save_data = serde_json::to_string(&save_data)?;
+    let mut file = File::create(SAVE_FILE)?;
+    file.write_all(save_data.as_bytes())?;
+    Ok(())
+}
+
+pub fn load_game() -> Result<(Game, Vec<GameObject>), SaveError> {
+    let mut json_save_state = String::new();
+    let mut file = File::open(SAVE_FILE)?;
+    file.read_to_string(&mut json_save_state)?;
+    parse_save(&json_save_state)
+}
+
+fn parse_save(json_save_state: &str) -> Result<(Game, Vec<GameObject>), SaveError> {
+    // Check the version before trying to read the rest,
+    // otherwise an old save would just show up as a field mismatch.
+    let value: serde_json::Value = serde_json::from_str(json_save_state)?;
+    let found = value.get("version").and_then(|version| version.as_u64()).unwrap_or(0);
+    if found != SAVE_VERSION as u64 {
+        return Err(SaveError::VersionMismatch { found, expected: SAVE_VERSION });
+    }
+
+    let mut save_data: SaveData = serde_json::from_value(value)?;
+    // A file that parses can still be missing things the game takes for granted.
+    if save_data.game_objects.get(PLAYER).and_then(|player| player.fighter).is_none() {
+        return Err(corrupt("there is no player"));
+    }
+    let map = &save_data.game.map;
+    if map.len() != MAP_WIDTH as usize || map.iter().any(|column| column.len() != MAP_HEIGHT as usize) {
+        return Err(corrupt("the map is the wrong size"));
+    }
+
+    // the FOV isn't saved, so compute it again on the loaded map
+    save_data.game.update_fov(&save_data.game_objects);
+    Ok((save_data.game, save_data.game_objects))
+}
+
+fn corrupt(reason: &str) -> SaveError {
+    SaveError::Corrupt(serde::de::Error::custom(reason))
+}
+
+// Once the player dies there is nothing left to continue, so the save is thrown away.
+pub fn delete_save() -> Result<(), SaveError> {
+    match fs::remove_file(SAVE_FILE) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(SaveError::Io(error)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::create_game;
+    use crate::monsters::builtin_monsters;
+    use super::*;
+
+    fn saved_game() -> serde_json::Value {
+        let (game, game_objects) = create_game(&builtin_monsters(), 1);
+        let save_data = SaveDataRef { version: SAVE_VERSION, game: &game, game_objects: &game_objects };
+        serde_json::to_value(save_data).unwrap()
+    }
+
+    #[test]
+    fn a_saved_game_loads_again() {
+        let (game, game_objects) = parse_save(&saved_game().to_string()).unwrap();
+        assert_eq!(game.map.len(), MAP_WIDTH as usize);
+        assert!(game_objects[PLAYER].fighter.is_some());
+    }
+
+    #[test]
+    fn old_versions_are_rejected() {
+        let mut save = saved_game();
+        save["version"] = (SAVE_VERSION - 1).into();
+        assert!(matches!(parse_save(&save.to_string()), Err(SaveError::VersionMismatch { .. })));
+    }
+
+    #[test]
+    fn a_save_without_a_player_is_corrupt() {
+        let mut save = saved_game();
+        save["game_objects"] = serde_json::json!([]);
+        assert!(matches!(parse_save(&save.to_string()), Err(SaveError::Corrupt(_))));
+    }
+
+    #[test]
+    fn a_map_of_the_wrong_size_is_corrupt() {
+        let mut save = saved_game();
+        save["game"]["map"].as_array_mut().unwrap().pop();
+        assert!(matches!(parse_save(&save.to_string()), Err(SaveError::Corrupt(_))));
+
+        let mut save = saved_game();
+        save["game"]["map"][3].as_array_mut().unwrap().pop();
+        assert!(matches!(parse_save(&save.to_string()), Err(SaveError::Corrupt(_))));
+    }
+}
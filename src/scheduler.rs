@@ -0,0 +1,164 @@
+// Speed in energy gained per tick. A creature with twice this acts twice as often.
+pub const NORMAL_SPEED: i32 = 100;
+// Energy one action costs.
+const ACTION_COST: i32 = 100;
+
+// Anything that takes turns. The energy it has saved up lives with the actor itself,
+// so nothing has to be kept in step when actors come and go.
+pub trait Actor {
+    fn speed(&self) -> i32;
+    fn energy(&self) -> i32;
+    fn set_energy(&mut self, energy: i32);
+}
+
+// Energy-based turns: time passes in ticks, every tick each actor gains as much energy as its speed,
+// and whoever has enough energy for an action takes one and pays for it.
+pub struct Scheduler {
+    action_cost: i32,
+}
+
+impl Scheduler {
+    pub fn new(action_cost: i32) -> Self {
+        Scheduler { action_cost }
+    }
+
+    // Let time pass until someone can act, and say who acts next. When several can, the one that comes
+    // first in the list goes first. Returns None if nobody will ever act.
+    // This doesn't spend any energy; call spend once the actor has acted.
+    pub fn next_actor<A: Actor>(&self, actors: &mut [A]) -> Option<usize> {
+        let ticks = actors
+            .iter()
+            .filter(|actor| actor.speed() > 0)
+            .map(|actor| {
+                let missing = (self.action_cost - actor.energy()).max(0);
+                // rounding up, the number of ticks until this actor has enough
+                (missing + actor.speed() - 1) / actor.speed()
+            })
+            .min()?;
+
+        if ticks > 0 {
+            for actor in actors.iter_mut().filter(|actor| actor.speed() > 0) {
+                actor.set_energy(actor.energy() + actor.speed() * ticks);
+            }
+        }
+
+        actors
+            .iter()
+            .position(|actor| actor.speed() > 0 && actor.energy() >= self.action_cost)
+    }
+
+    pub fn spend<A: Actor>(&self, actor: &mut A) {
+        actor.set_energy(actor.energy() - self.action_cost);
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new(ACTION_COST)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestActor {
+        speed: i32,
+        energy: i32,
+    }
+
+    impl Actor for TestActor {
+        fn speed(&self) -> i32 {
+            self.speed
+        }
+
+        fn energy(&self) -> i32 {
+            self.energy
+        }
+
+        fn set_energy(&mut self, energy: i32) {
+            self.energy = energy;
+        }
+    }
+
+    fn actors(speeds: &[i32]) -> Vec<TestActor> {
+        speeds.iter().map(|&speed| TestActor { speed, energy: 0 }).collect()
+    }
+
+    // who acts, in order, for the given number of actions
+    fn run(scheduler: &Scheduler, actors: &mut [TestActor], actions: usize) -> Vec<usize> {
+        (0..actions)
+            .map(|_| {
+                let index = scheduler.next_actor(actors).unwrap();
+                scheduler.spend(&mut actors[index]);
+                index
+            })
+            .collect()
+    }
+
+    #[test]
+    fn equal_speeds_take_turns_in_order() {
+        let scheduler = Scheduler::default();
+        let mut actors = actors(&[NORMAL_SPEED, NORMAL_SPEED, NORMAL_SPEED]);
+        assert_eq!(run(&scheduler, &mut actors, 6), vec![0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn fast_actors_act_twice_and_slow_ones_every_other_turn() {
+        let scheduler = Scheduler::default();
+        // the player, a bat and a zombie
+        let mut actors = actors(&[NORMAL_SPEED, 2 * NORMAL_SPEED, NORMAL_SPEED / 2]);
+        let order = run(&scheduler, &mut actors, 14);
+        assert_eq!(order, vec![0, 1, 1, 0, 1, 1, 2, 0, 1, 1, 0, 1, 1, 2]);
+        assert_eq!(order.iter().filter(|&&index| index == 0).count(), 4);
+        assert_eq!(order.iter().filter(|&&index| index == 1).count(), 8);
+        assert_eq!(order.iter().filter(|&&index| index == 2).count(), 2);
+    }
+
+    #[test]
+    fn ties_go_to_the_first_in_the_list() {
+        let scheduler = Scheduler::default();
+        let mut actors = actors(&[NORMAL_SPEED, NORMAL_SPEED, NORMAL_SPEED]);
+        // all of them can act, however much energy they have to spare
+        actors[0].energy = 100;
+        actors[1].energy = 120;
+        actors[2].energy = 150;
+        assert_eq!(run(&scheduler, &mut actors, 3), vec![0, 1, 2]);
+        assert_eq!(actors[2].energy, 50);
+    }
+
+    #[test]
+    fn speed_changes_apply_from_the_next_tick() {
+        let scheduler = Scheduler::default();
+        let mut actors = actors(&[NORMAL_SPEED, NORMAL_SPEED / 2]);
+        assert_eq!(run(&scheduler, &mut actors, 1), vec![0]);
+        assert_eq!(actors[1].energy, 50);
+
+        // hasted half way to its turn: the energy saved up so far is kept
+        actors[1].speed = 2 * NORMAL_SPEED;
+        assert_eq!(run(&scheduler, &mut actors, 3), vec![0, 1, 1]);
+        assert_eq!(actors[1].energy, 50);
+
+        // and slowed right down again
+        actors[1].speed = NORMAL_SPEED / 4;
+        assert_eq!(run(&scheduler, &mut actors, 3), vec![0, 0, 1]);
+        assert_eq!(actors[1].energy, 0);
+    }
+
+    #[test]
+    fn actors_without_speed_never_act() {
+        let scheduler = Scheduler::default();
+        let mut actors = actors(&[0, NORMAL_SPEED, 0]);
+        assert_eq!(run(&scheduler, &mut actors, 3), vec![1, 1, 1]);
+        assert_eq!(scheduler.next_actor(&mut [TestActor { speed: 0, energy: 0 }]), None);
+    }
+
+    #[test]
+    fn the_same_speeds_always_give_the_same_order() {
+        let scheduler = Scheduler::default();
+        let speeds = [NORMAL_SPEED, 130, 70, 100, 45];
+        let first = run(&scheduler, &mut actors(&speeds), 100);
+        let second = run(&scheduler, &mut actors(&speeds), 100);
+        assert_eq!(first, second);
+    }
+}
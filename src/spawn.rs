@@ -0,0 +1,188 @@
+use rand::Rng;
+use serde::Deserialize;
+use crate::item::Item;
+use crate::monsters::MonsterTemplate;
+
+// The value a spawn setting takes from a given dungeon level onwards.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Transition {
+    pub level: u32,
+    pub value: u32,
+}
+
+// Returns the value that applies to the given dungeon level.
+// The table must be sorted by level; levels before the first transition get 0.
+pub fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
+    table
+        .iter()
+        .rev()
+        .find(|transition| level >= transition.level)
+        .map_or(0, |transition| transition.value)
+}
+
+// Pick one of the choices at random, with a probability proportional to its weight.
+// Returns None if there is nothing with a non-zero weight to choose from.
+pub fn weighted_choice<'a, T, R: Rng>(choices: &'a [(T, u32)], rng: &mut R) -> Option<&'a T> {
+    let total_weight: u32 = choices.iter().map(|&(_, weight)| weight).sum();
+    if total_weight == 0 {
+        return None;
+    }
+
+    // walk the choices until the roll falls inside one of them
+    let mut roll = rng.gen_range(0, total_weight);
+    for (choice, weight) in choices {
+        if roll < *weight {
+            return Some(choice);
+        }
+        roll -= weight;
+    }
+    unreachable!("the roll is always below the total weight")
+}
+
+// All the spawn settings live below, so balancing the dungeon only means editing this file.
+
+// maximum number of monsters per room
+pub fn max_room_monsters(level: u32) -> u32 {
+    from_dungeon_level(
+        &[
+            Transition { level: 1, value: 2 },
+            Transition { level: 4, value: 3 },
+            Transition { level: 6, value: 5 },
+        ],
+        level,
+    )
+}
+
+// chance of each monster (the weights themselves come with the monster templates)
+pub fn monster_table(monster_templates: &[MonsterTemplate], level: u32) -> Vec<(&MonsterTemplate, u32)> {
+    monster_templates
+        .iter()
+        .map(|template| (template, from_dungeon_level(&template.spawn_weights, level)))
+        .collect()
+}
+
+// maximum number of items per room
+pub fn max_room_items(level: u32) -> u32 {
+    from_dungeon_level(
+        &[
+            Transition { level: 1, value: 1 },
+            Transition { level: 4, value: 2 },
+        ],
+        level,
+    )
+}
+
+// chance of each item (by default they have a chance of 0 at level 1, which then goes up)
+pub fn item_table(level: u32) -> Vec<(Item, u32)> {
+    vec![
+        (Item::Heal, 35),
+        (
+            Item::Regeneration,
+            from_dungeon_level(&[Transition { level: 2, value: 10 }], level),
+        ),
+        (
+            Item::Haste,
+            from_dungeon_level(&[Transition { level: 3, value: 10 }], level),
+        ),
+        (
+            Item::Lightning,
+            from_dungeon_level(&[Transition { level: 4, value: 25 }], level),
+        ),
+        (
+            Item::Fireball,
+            from_dungeon_level(&[Transition { level: 6, value: 25 }], level),
+        ),
+        (
+            Item::Confuse,
+            from_dungeon_level(&[Transition { level: 2, value: 10 }], level),
+        ),
+        (
+            Item::Bow,
+            from_dungeon_level(&[Transition { level: 2, value: 5 }], level),
+        ),
+        (Item::ThrowingKnife, 10),
+        (
+            Item::Sword,
+            from_dungeon_level(&[Transition { level: 4, value: 5 }], level),
+        ),
+        (
+            Item::Shield,
+            from_dungeon_level(&[Transition { level: 8, value: 15 }], level),
+        ),
+        (
+            Item::Helmet,
+            from_dungeon_level(&[Transition { level: 3, value: 5 }], level),
+        ),
+        (
+            Item::Armor,
+            from_dungeon_level(&[Transition { level: 6, value: 5 }], level),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, XorShiftRng};
+    use crate::monsters::builtin_monsters;
+    use super::*;
+
+    fn test_rng() -> XorShiftRng {
+        XorShiftRng::from_seed([1, 2, 3, 4])
+    }
+
+    #[test]
+    fn from_dungeon_level_uses_the_latest_transition() {
+        let table = [
+            Transition { level: 2, value: 10 },
+            Transition { level: 5, value: 30 },
+        ];
+        assert_eq!(from_dungeon_level(&table, 1), 0);
+        assert_eq!(from_dungeon_level(&table, 2), 10);
+        assert_eq!(from_dungeon_level(&table, 4), 10);
+        assert_eq!(from_dungeon_level(&table, 5), 30);
+        assert_eq!(from_dungeon_level(&table, 99), 30);
+    }
+
+    #[test]
+    fn weighted_choice_with_no_weight_chooses_nothing() {
+        let mut rng = test_rng();
+        let empty: [(char, u32); 0] = [];
+        assert_eq!(weighted_choice(&empty, &mut rng), None);
+        assert_eq!(weighted_choice(&[('a', 0), ('b', 0)], &mut rng), None);
+    }
+
+    #[test]
+    fn weighted_choice_never_picks_zero_weights() {
+        let mut rng = test_rng();
+        let choices = [('a', 0), ('b', 7), ('c', 0)];
+        for _ in 0..1000 {
+            assert_eq!(weighted_choice(&choices, &mut rng), Some(&'b'));
+        }
+    }
+
+    #[test]
+    fn weighted_choice_follows_the_weights() {
+        let mut rng = test_rng();
+        let choices = [('a', 1), ('b', 3)];
+        let draws = 10_000;
+        let b_count = (0..draws)
+            .filter(|_| weighted_choice(&choices, &mut rng) == Some(&'b'))
+            .count();
+        // expect about 75% 'b'
+        let b_ratio = b_count as f32 / draws as f32;
+        assert!((0.72..0.78).contains(&b_ratio), "b was chosen {} of the time", b_ratio);
+    }
+
+    #[test]
+    fn trolls_get_more_common_deeper_down() {
+        let monster_templates = builtin_monsters();
+        let troll_weight = |level| {
+            monster_table(&monster_templates, level)
+                .into_iter()
+                .find(|(template, _)| template.name == "Troll")
+                .map_or(0, |(_, weight)| weight)
+        };
+        assert!(troll_weight(1) < troll_weight(5));
+        assert!(troll_weight(5) < troll_weight(7));
+    }
+}
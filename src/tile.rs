@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 // The #[derive(…)] automatically implements certain behaviors (Rust calls them traits, other languages use interfaces) you list there.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Tile {
     pub is_blocked: bool,
     pub is_sight_blocked: bool,
@@ -22,4 +24,19 @@ impl Tile {
             is_explored: false,
         }
     }
-}
\ No newline at end of file
+}
+
+// Build a map from rows of text for tests: '#' is a wall, anything else is floor.
+#[cfg(test)]
+pub fn map_from(rows: &[&str]) -> crate::game::Map {
+    let width = rows[0].len();
+    let mut map = vec![vec![Tile::empty(); rows.len()]; width];
+    for (y, row) in rows.iter().enumerate() {
+        for (x, char) in row.chars().enumerate() {
+            if char == '#' {
+                map[x][y] = Tile::wall();
+            }
+        }
+    }
+    map
+}
@@ -0,0 +1,777 @@
+// Playing the game in a window: drawing it, reading the keys and mouse, the menus and the main loop.
+
+use std::io;
+use std::mem;
+use std::thread;
+use std::time::Duration;
+use tcod::colors::*;
+use tcod::console::*;
+use tcod::input::{self, Event, Key, Mouse};
+use crate::activity::*;
+use crate::fov::FovAlgorithm;
+use crate::game::*;
+use crate::game_object::*;
+use crate::gui::*;
+use crate::item::*;
+use crate::item_use::use_item;
+use crate::keybindings::*;
+use crate::monsters::*;
+use crate::ranged::line_of_fire;
+use crate::save::*;
+use crate::{MAP_HEIGHT, MAP_WIDTH, PLAYER};
+
+const FPS_LIMIT: i32 = 100;
+
+pub const SCREEN_WIDTH: i32 = 80;
+pub const SCREEN_HEIGHT: i32 = 50;
+
+// GUI
+const BAR_WIDTH: i32 = 20;
+const PANEL_HEIGHT: i32 = 7;
+const PANEL_Y: i32 = SCREEN_HEIGHT - PANEL_HEIGHT;
+
+// This is so it appears to the right of the health bar, and fills up the rest of the space.
+const MSG_X: i32 = BAR_WIDTH + 2;
+const MSG_WIDTH: i32 = SCREEN_WIDTH - BAR_WIDTH - 2;
+const MSG_HEIGHT: usize = PANEL_HEIGHT as usize - 1;
+
+const COLOR_DARK_WALL: Color = Color { r: 0, g: 0, b: 100 };
+const COLOR_LIGHT_WALL: Color = Color { r: 130, g: 110, b: 50 };
+const COLOR_DARK_GROUND: Color = Color { r: 50, g: 50, b: 150 };
+const COLOR_LIGHT_GROUND: Color = Color { r: 200, g: 180, b: 50 };
+
+// Main Menu
+const MAIN_MENU_WIDTH: i32 = 24;
+const INVENTORY_WIDTH: i32 = 50;
+const LEVEL_SCREEN_WIDTH: i32 = 40;
+const HELP_SCREEN_WIDTH: i32 = 50;
+
+// How long a projectile is shown on each tile it flies over.
+const PROJECTILE_FRAME_TIME: Duration = Duration::from_millis(15);
+
+// Experience and level-ups
+const LEVEL_UP_BASE: i32 = 200;
+const LEVEL_UP_FACTOR: i32 = 150;
+
+pub struct Tcod {
+    root: Root,
+    // Everything is drawn to the root console (eventually).
+    console: Offscreen,
+    // We'll put our GUI here
+    panel: Offscreen,
+    // The latest input events, polled once per frame.
+    key: Key,
+    mouse: Mouse,
+    // Which key does what, from the keybindings file.
+    keybindings: KeyBindings,
+}
+
+fn handle_keys(tcod: &mut Tcod, game: &mut Game, game_objects: &mut Vec<GameObject>) -> PlayerAction {
+    use tcod::input::KeyCode::Enter;
+    use crate::game::PlayerAction::*;
+
+    let key = tcod.key;
+    let player_alive = game_objects[PLAYER].alive;
+
+    // Travel to an explored tile by clicking on it
+    let (mouse_x, mouse_y) = (tcod.mouse.cx as i32, tcod.mouse.cy as i32);
+    if tcod.mouse.lbutton_pressed && player_alive && mouse_x < MAP_WIDTH && mouse_y < MAP_HEIGHT {
+        travel_to((mouse_x, mouse_y), game, game_objects);
+        return DidntTakeTurn;
+    }
+
+    // Fullscreen is about the window rather than the game, so it can't be rebound
+    if let Key { code: Enter, alt: true, .. } = key {
+        let fullscreen = tcod.root.is_fullscreen();
+        tcod.root.set_fullscreen(!fullscreen);
+        return DidntTakeTurn;
+    }
+
+    let action = match tcod.keybindings.action_for(key) {
+        Some(action) => action,
+        None => return DidntTakeTurn,
+    };
+    if let (Some((dx, dy)), true) = (action.direction(), player_alive) {
+        player_move_or_attack(dx, dy, game, game_objects);
+        return TookTurn;
+    }
+
+    match (action, player_alive) {
+        // Exit
+        (Action::Quit, _) => Exit,
+        // List the keys and what they do
+        (Action::Help, _) => {
+            let help = format!("Keys\n\n{}\n", tcod.keybindings.describe().join("\n"));
+            msgbox(&help, HELP_SCREEN_WIDTH, &mut tcod.root);
+            DidntTakeTurn
+        }
+        // Look back through everything that happened
+        (Action::MessageLog, _) => {
+            message_log(&game.messages, &mut tcod.root);
+            DidntTakeTurn
+        }
+        // Wait a turn in place
+        (Action::Wait, true) => TookTurn,
+        // Pick up an item
+        (Action::PickUp, true) => {
+            // pick up an item under the player
+            let item_id = game_objects
+                .iter()
+                .position(|game_object|
+                    game_object.get_position() == game_objects[PLAYER].get_position()
+                        && game_object.item.is_some());
+            match item_id {
+                Some(item_id) => {
+                    pick_item_up(item_id, game, game_objects);
+                    TookTurn
+                }
+                // nothing here, so no time passes
+                None => DidntTakeTurn,
+            }
+        }
+        // Show the inventory and use the chosen item
+        (Action::Inventory, true) => {
+            let inventory_index = inventory_menu(
+                &game.inventory,
+                "Press the key next to an item to use it, or any other to cancel.\n",
+                INVENTORY_WIDTH,
+                &mut tcod.root,
+            );
+            // a use that was called off, like closing the targeting screen, doesn't cost a turn
+            match inventory_index {
+                Some(inventory_index) if use_item(inventory_index, tcod, game, game_objects) => TookTurn,
+                _ => DidntTakeTurn,
+            }
+        }
+        // Shoot the bow, or throw something if there's no bow
+        (Action::Fire, true) => {
+            let weapon = game
+                .inventory
+                .iter()
+                .position(|item| item.item == Some(Item::Bow))
+                .or_else(|| game.inventory.iter().position(|item| item.item == Some(Item::ThrowingKnife)));
+            match weapon {
+                Some(inventory_index) if use_item(inventory_index, tcod, game, game_objects) => TookTurn,
+                Some(_) => DidntTakeTurn,
+                None => {
+                    game.messages.add("You have nothing to shoot or throw.", WHITE);
+                    DidntTakeTurn
+                }
+            }
+        }
+        // Go down the stairs, if the player is on them
+        (Action::Descend, true) => {
+            let player_on_stairs = game_objects
+                .iter()
+                .any(|game_object|
+                    game_object.get_position() == game_objects[PLAYER].get_position()
+                        && game_object.name == "stairs");
+            if player_on_stairs {
+                next_level(game, game_objects);
+            }
+            DidntTakeTurn
+        }
+        // Explore until something interesting happens
+        (Action::Explore, true) => {
+            start_activity(Activity::Exploring, game, game_objects);
+            DidntTakeTurn
+        }
+        // Travel back to where the last monster died
+        (Action::TravelToCorpse, true) => {
+            travel_to_last_corpse(game, game_objects);
+            DidntTakeTurn
+        }
+        // Show the inventory and drop the chosen item
+        (Action::Drop, true) => {
+            let inventory_index = inventory_menu(
+                &game.inventory,
+                "Press the key next to an item to drop it, or any other to cancel.\n",
+                INVENTORY_WIDTH,
+                &mut tcod.root,
+            );
+            if let Some(inventory_index) = inventory_index {
+                drop_item(inventory_index, game, game_objects);
+                TookTurn
+            } else {
+                DidntTakeTurn
+            }
+        }
+
+        // Everything else
+        // _ => {} // This means "everything else" => "nothing happens"
+        _ => DidntTakeTurn
+    }
+}
+
+fn render_all(tcod: &mut Tcod, game: &Game, game_objects: &[GameObject]) {
+    // Render Tiles
+    // Go through all tiles, and set their background color:
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            let visible = game.fov.is_in_fov(x, y);
+            let x_index = x as usize;
+            let y_index = y as usize;
+            let blocks_sight = game.map[x_index][y_index].is_sight_blocked;
+
+            let color = match (visible, blocks_sight) {
+                // outside FOV
+                (false, true) => COLOR_DARK_WALL,
+                (false, false) => COLOR_DARK_GROUND,
+                // inside FOV
+                (true, true) => COLOR_LIGHT_WALL,
+                (true, false) => COLOR_LIGHT_GROUND
+            };
+
+            // (tiles get marked as explored when the FOV is updated)
+            if game.map[x_index][y_index].is_explored {
+                // show explored tiles only
+                tcod.console.set_char_background(x, y, color, BackgroundFlag::Set);
+            }
+        }
+    }
+
+    // Instead of going through the objects list we clone it into a mutable vector
+    // (render_all is taking &[Object] so it can’t change the list directly, nor should it).
+    // Then we sort the vector such that all non-blocking objects come before all
+    // the blocking ones. Since we can’t have two blocking objects on the same tile,
+    // this will make sure that our player and monsters won’t get overwritten by corpses.
+    let mut to_draw: Vec<_> = game_objects
+        .iter()
+        // filter out game objects that arent within FOV since we're not going to render them.
+        // (always visible objects are still drawn once their tile has been explored)
+        .filter(|game_object| {
+            game.fov.is_in_fov(game_object.x, game_object.y)
+                || (game_object.always_visible
+                && game.map[game_object.x as usize][game_object.y as usize].is_explored)
+        })
+        .collect();
+    // sort so that non-blocking objects come first.
+    to_draw.sort_by(|game_object_1, game_object_2|
+        game_object_1.blocks_tile.cmp(&game_object_2.blocks_tile));
+
+    // draw all the objects in the list that are within the FOV:
+    for game_object in to_draw {
+        game_object.draw(&mut tcod.console);
+    }
+
+    // GUI
+    // Prepare to render the GUI Panel
+    tcod.panel.set_default_background(BLACK);
+    tcod.panel.clear();
+    // Show the players stats
+    let hp = game_objects[PLAYER]
+        .fighter
+        .map_or(0, |fighter| fighter.hp);
+    let max_hp = game_objects[PLAYER].max_hp(game);
+    render_bar(
+        &mut tcod.panel,
+        1, 1,
+        BAR_WIDTH,
+        "HP",
+        hp,
+        max_hp,
+        LIGHT_RED,
+        DARKER_RED,
+    );
+    let xp = game_objects[PLAYER]
+        .fighter
+        .map_or(0, |fighter| fighter.xp);
+    render_bar(
+        &mut tcod.panel,
+        1, 2,
+        BAR_WIDTH,
+        "XP",
+        xp,
+        level_up_xp(game_objects[PLAYER].level),
+        LIGHT_VIOLET,
+        DARKER_VIOLET,
+    );
+    tcod.panel.print_ex(
+        1,
+        3,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Dungeon level: {}", game.dungeon_level),
+    );
+    tcod.panel.print_ex(
+        1,
+        4,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        format!("Seed: {}", game.seed),
+    );
+    // a letter for each effect on the player, with the turns it has left
+    let mut x = 1;
+    for status_effect in &game_objects[PLAYER].effects {
+        let text = format!("{}{}", status_effect.effect.letter(), status_effect.turns);
+        tcod.panel.set_default_foreground(status_effect.effect.color());
+        tcod.panel.print_ex(x, 5, BackgroundFlag::None, TextAlignment::Left, &text);
+        x += text.len() as i32 + 1;
+    }
+
+    // display names of objects under the mouse
+    tcod.panel.set_default_foreground(LIGHT_GREY);
+    tcod.panel.print_ex(
+        1,
+        0,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        get_names_under_mouse(tcod.mouse, game_objects, &game.fov),
+    );
+
+    // Print the Messages
+    let mut y = MSG_HEIGHT as i32;
+    // We’re going through the messages backwards (starting with the last message),
+    // because we don’t know if we get to print all.
+    // So we first calculate the height of the message (in case it gets wrapped),
+    // we draw it at the corresponding y position by subtracting the height and then repeat.
+    for (msg, color) in game.messages.iter().rev() {
+        let msg_height = tcod.panel.get_height_rect(MSG_X, y, MSG_WIDTH, 0, &msg);
+        y -= msg_height;
+        if y < 0 {
+            break;
+        }
+        tcod.panel.set_default_foreground(color);
+        tcod.panel.print_rect(MSG_X, y, MSG_WIDTH, 0, &msg);
+    }
+
+    // Blit the contents of "console" to the root console.
+    blit(
+        &tcod.console,
+        (0, 0),
+        (MAP_WIDTH, MAP_HEIGHT),
+        &mut tcod.root,
+        (0, 0),
+        1.0,
+        1.0,
+    );
+
+    // Blit the contents of "panel" to the root console.
+    blit(
+        &tcod.panel,
+        (0, 0),
+        (SCREEN_WIDTH, PANEL_Y),
+        &mut tcod.root,
+        (0, PANEL_Y),
+        1.0,
+        1.0,
+    );
+}
+
+// Let the player pick a tile with the mouse, returning its coordinates on a left-click.
+// Only tiles in the player's FOV (and within max_range, if given) can be picked.
+// Right-click or Escape cancels and returns None.
+pub fn target_tile(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    game_objects: &[GameObject],
+    max_range: Option<f32>,
+) -> Option<(i32, i32)> {
+    use tcod::input::KeyCode::Escape;
+    // forget the click or key that opened the targeting, so it doesn't pick a target by itself
+    tcod.mouse = Default::default();
+    tcod.key = Default::default();
+    loop {
+        // render the screen. this erases the inventory and shows the names of
+        // objects under the mouse.
+        tcod.root.flush();
+        let event = input::check_for_event(input::KEY_PRESS | input::MOUSE).map(|e| e.1);
+        match event {
+            Some(Event::Mouse(mouse)) => tcod.mouse = mouse,
+            Some(Event::Key(key)) => tcod.key = key,
+            None => tcod.key = Default::default(),
+        }
+        render_all(tcod, game, game_objects);
+
+        let (x, y) = (tcod.mouse.cx as i32, tcod.mouse.cy as i32);
+
+        // accept the target if the player clicked in FOV, and in case a range
+        // is specified, if it's in that range
+        let in_map = (0..MAP_WIDTH).contains(&x) && (0..MAP_HEIGHT).contains(&y);
+        let in_fov = in_map && game.fov.is_in_fov(x, y);
+        let in_range = max_range.is_none_or(|range| game_objects[PLAYER].distance(x, y) <= range);
+        if tcod.mouse.lbutton_pressed && in_fov && in_range {
+            return Some((x, y));
+        }
+
+        if tcod.mouse.rbutton_pressed || tcod.key.code == Escape {
+            // cancel if the player right-clicked or pressed Escape
+            return None;
+        }
+    }
+}
+
+// Pick a target for something shot or thrown by the player, showing where it would fly.
+// Tab goes through the monsters in range, closest first, and Enter or the fire key shoots at the chosen one;
+// a left click shoots at any tile in range instead. Right-click or Escape cancels.
+pub fn target_ranged(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    game_objects: &[GameObject],
+    range: i32,
+) -> Option<(i32, i32)> {
+    use tcod::input::KeyCode::{Enter, Escape, Tab};
+    let player = &game_objects[PLAYER];
+    let in_range = |x: i32, y: i32| game.fov.is_in_fov(x, y) && player.distance(x, y) <= range as f32;
+    let mut targets: Vec<&GameObject> = game_objects
+        .iter()
+        .enumerate()
+        .filter(|&(id, game_object)| id != PLAYER && game_object.fighter.is_some() && in_range(game_object.x, game_object.y))
+        .map(|(_, game_object)| game_object)
+        .collect();
+    targets.sort_by(|first, second| player.distance_to(first).total_cmp(&player.distance_to(second)));
+    let targets: Vec<(i32, i32)> = targets.iter().map(|game_object| game_object.get_position()).collect();
+
+    game.messages.add(
+        "Tab picks a target and Enter shoots, or left-click a tile; right-click / Escape to cancel.",
+        LIGHT_CYAN,
+    );
+    let mut chosen = 0;
+    tcod.mouse = Default::default();
+    tcod.key = Default::default();
+    loop {
+        tcod.root.flush();
+        let event = input::check_for_event(input::KEY_PRESS | input::MOUSE).map(|e| e.1);
+        tcod.key = Default::default();
+        tcod.mouse.lbutton_pressed = false;
+        tcod.mouse.rbutton_pressed = false;
+        match event {
+            Some(Event::Mouse(mouse)) => tcod.mouse = mouse,
+            Some(Event::Key(key)) => tcod.key = key,
+            None => {}
+        }
+        tcod.console.clear();
+        render_all(tcod, game, game_objects);
+
+        // highlight the way to the chosen target
+        let target = targets.get(chosen).copied();
+        if let Some(target) = target {
+            let shot = line_of_fire(&game.map, game_objects, player.get_position(), target);
+            for &(x, y) in &shot.path {
+                tcod.root.set_char_background(x, y, DARK_RED, BackgroundFlag::Set);
+            }
+        }
+
+        let (x, y) = (tcod.mouse.cx as i32, tcod.mouse.cy as i32);
+        let in_map = (0..MAP_WIDTH).contains(&x) && (0..MAP_HEIGHT).contains(&y);
+        if tcod.mouse.lbutton_pressed && in_map && in_range(x, y) {
+            return Some((x, y));
+        }
+        let fire_key = tcod.keybindings.action_for(tcod.key) == Some(Action::Fire);
+        if (tcod.key.code == Enter || fire_key) && target.is_some() {
+            return target;
+        }
+        if tcod.key.code == Tab && !targets.is_empty() {
+            chosen = (chosen + 1) % targets.len();
+        }
+        if tcod.mouse.rbutton_pressed || tcod.key.code == Escape {
+            return None;
+        }
+    }
+}
+
+// Show everything shot since the last frame flying along its path, one tile at a time.
+fn animate_projectiles(tcod: &mut Tcod, game: &mut Game, game_objects: &[GameObject]) {
+    tcod.console.clear();
+    for projectile in mem::take(&mut game.projectiles) {
+        for &(x, y) in projectile.path.iter().filter(|&&(x, y)| game.fov.is_in_fov(x, y)) {
+            render_all(tcod, game, game_objects);
+            // draw it over the map, then show the map again the same way render_all does
+            tcod.console.set_default_foreground(projectile.color);
+            tcod.console.put_char(x, y, projectile.char, BackgroundFlag::None);
+            blit(&tcod.console, (0, 0), (MAP_WIDTH, MAP_HEIGHT), &mut tcod.root, (0, 0), 1.0, 1.0);
+            tcod.root.flush();
+            thread::sleep(PROJECTILE_FRAME_TIME);
+            // so the next frame doesn't still show it here
+            tcod.console.clear();
+        }
+    }
+}
+
+// Like target_tile, but only accepts a tile with a monster on it and returns that monster.
+pub fn target_monster(
+    tcod: &mut Tcod,
+    game: &mut Game,
+    game_objects: &[GameObject],
+    max_range: Option<f32>,
+) -> Option<usize> {
+    loop {
+        match target_tile(tcod, game, game_objects, max_range) {
+            Some((x, y)) => {
+                // return the first clicked monster, otherwise continue looping
+                for (id, game_object) in game_objects.iter().enumerate() {
+                    if game_object.get_position() == (x, y) && game_object.fighter.is_some() && id != PLAYER {
+                        return Some(id);
+                    }
+                }
+            }
+            None => return None,
+        }
+    }
+}
+
+// The experience needed to go from the given level to the next one.
+fn level_up_xp(level: i32) -> i32 {
+    LEVEL_UP_BASE + level * LEVEL_UP_FACTOR
+}
+
+fn level_up(tcod: &mut Tcod, game: &mut Game, game_objects: &mut [GameObject]) {
+    let player = &mut game_objects[PLAYER];
+    let level_up_xp = level_up_xp(player.level);
+    // see if the player's experience is enough to level-up
+    if player.fighter.map_or(0, |fighter| fighter.xp) >= level_up_xp {
+        // it is! level up
+        player.level += 1;
+        game.messages.add(
+            format!(
+                "Your battle skills grow stronger! You reached level {}!",
+                player.level
+            ),
+            YELLOW,
+        );
+        let fighter = player.fighter.as_mut().unwrap();
+        let mut choice = None;
+        while choice.is_none() {
+            // keep asking until a choice is made
+            choice = menu(
+                "Level up! Choose a stat to raise:\n",
+                &[
+                    format!("Constitution (+20 HP, from {})", fighter.base_max_hp),
+                    format!("Strength (+1 attack, from {})", fighter.base_power),
+                    format!("Agility (+1 defense, from {})", fighter.base_defense),
+                ],
+                LEVEL_SCREEN_WIDTH,
+                &mut tcod.root,
+            );
+        }
+        fighter.xp -= level_up_xp;
+        match choice.unwrap() {
+            0 => {
+                fighter.base_max_hp += 20;
+                fighter.hp += 20;
+            }
+            1 => {
+                fighter.base_power += 1;
+            }
+            2 => {
+                fighter.base_defense += 1;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn play_game(tcod: &mut Tcod, game: &mut Game, game_objects: &mut Vec<GameObject>) {
+    // Game Loop
+    while !tcod.root.window_closed() {
+        // Clear the console from the previous frame.
+        tcod.console.clear();
+
+        // Poll for input without blocking, so the mouse look updates as the cursor moves.
+        // Key presses and clicks only count for the frame they happen in.
+        tcod.key = Default::default();
+        tcod.mouse.lbutton_pressed = false;
+        tcod.mouse.rbutton_pressed = false;
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+            Some((_, Event::Mouse(mouse))) => tcod.mouse = mouse,
+            Some((_, Event::Key(key))) => tcod.key = key,
+            _ => {}
+        }
+
+        // Render the screen.
+        render_all(tcod, game, game_objects);
+
+        // Draw everything at once.
+        tcod.root.flush();
+
+        // level up if needed
+        level_up(tcod, game, game_objects);
+
+        // The player died last turn: let them read the log, then go back to the main menu.
+        if !game_objects[PLAYER].alive {
+            tcod.root.wait_for_keypress(true);
+            break;
+        }
+
+        // Handle Input and Exit if needed.
+        // player turn: keep doing what the player is doing, until any key or click interrupts it
+        let watch = Watch::new(game, game_objects);
+        let interrupted = tcod.key.code != input::KeyCode::NoKey
+            || tcod.mouse.lbutton_pressed
+            || tcod.mouse.rbutton_pressed;
+        let player_action = match (&game.activity, interrupted) {
+            (None, _) => handle_keys(tcod, game, game_objects),
+            (Some(_), false) => continue_activity(game, game_objects),
+            (Some(_), true) => {
+                stop_activity(game);
+                PlayerAction::DidntTakeTurn
+            }
+        };
+        animate_projectiles(tcod, game, game_objects);
+        if player_action == PlayerAction::Exit {
+            break;
+        }
+
+        if game_objects[PLAYER].alive && player_action != PlayerAction::DidntTakeTurn {
+            // monsters take their turn
+            end_player_turn(game, game_objects);
+            animate_projectiles(tcod, game, game_objects);
+            check_activity(&watch, game, game_objects);
+        }
+    }
+
+    // However the game was left, closing the window included, a living player's game is kept for next time.
+    if game_objects[PLAYER].alive {
+        if let Err(error) = save_game(game, game_objects) {
+            println!("Could not save the game: {}", error);
+        }
+    } else if let Err(error) = delete_save() {
+        println!("Could not delete the save file: {}", error);
+    }
+}
+
+fn main_menu(tcod: &mut Tcod, monster_templates: &[MonsterTemplate], options: &Options) {
+    while !tcod.root.window_closed() {
+        // show the game's title
+        tcod.root.set_default_background(BLACK);
+        tcod.root.clear();
+        tcod.root.set_default_foreground(LIGHT_YELLOW);
+        tcod.root.print_ex(
+            SCREEN_WIDTH / 2,
+            SCREEN_HEIGHT / 2 - 4,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            "RUSTY ROGUELIKE",
+        );
+
+        // show options and wait for the player's choice
+        let choices = &["New Game", "Continue", "Quit"];
+        let choice = menu("", choices, MAIN_MENU_WIDTH, &mut tcod.root);
+
+        match choice {
+            Some(0) => {
+                // new game
+                // use the seed from the command line if there was one, otherwise pick a fresh one
+                let seed = options.seed.unwrap_or_else(rand::random);
+                let (mut game, mut game_objects) = create_game(monster_templates, seed);
+                game.fov.algorithm = options.fov_algorithm;
+                game.update_fov(&game_objects);
+                play_game(tcod, &mut game, &mut game_objects);
+            }
+            Some(1) => {
+                // load the last game
+                match load_game() {
+                    Ok((mut game, mut game_objects)) => {
+                        game.monster_templates = monster_templates.to_vec();
+                        game.fov.algorithm = options.fov_algorithm;
+                        game.update_fov(&game_objects);
+                        play_game(tcod, &mut game, &mut game_objects);
+                    }
+                    Err(SaveError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+                        msgbox("\nNo saved game to load.\n", MAIN_MENU_WIDTH, &mut tcod.root);
+                    }
+                    Err(error) => {
+                        msgbox(&format!("\n{}\n", error), MAIN_MENU_WIDTH * 2, &mut tcod.root);
+                    }
+                }
+            }
+            Some(2) => {
+                // quit
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Settings that can be given on the command line.
+struct Options {
+    seed: Option<u64>,
+    fov_algorithm: FovAlgorithm,
+}
+
+// Read the optional `--seed <number>` and `--fov <shadowcasting|symmetric>` command line arguments.
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        seed: None,
+        fov_algorithm: FovAlgorithm::Shadowcasting,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let value = args.next().ok_or("--seed needs a number after it")?;
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("\"{}\" is not a valid seed", value))?;
+                options.seed = Some(value);
+            }
+            "--fov" => {
+                let value = args.next().ok_or("--fov needs an algorithm after it")?;
+                options.fov_algorithm = match value.as_str() {
+                    "shadowcasting" => FovAlgorithm::Shadowcasting,
+                    "symmetric" => FovAlgorithm::Symmetric,
+                    _ => return Err(format!("\"{}\" is not a known FOV algorithm", value)),
+                };
+            }
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+    Ok(options)
+}
+
+// The whole game, from the command line to the main menu; main.rs just calls this.
+// The rest of the library is here for the arena, which fights monsters against each other without a window.
+pub fn run() {
+    println!("Starting Game!");
+
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("Usage: roguelike [--seed <number>] [--fov <shadowcasting|symmetric>]");
+            std::process::exit(1);
+        }
+    };
+
+    // Check the monster definitions before opening a window, so mistakes are easy to read.
+    let monster_templates = match load_monsters() {
+        Ok(monster_templates) => monster_templates,
+        Err(error) => {
+            eprintln!("Could not load the monster definitions: {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let keybindings = match load_keybindings() {
+        Ok(keybindings) => keybindings,
+        Err(error) => {
+            eprintln!("Could not load the keybindings: {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    // Set tcod lib fps limit
+    tcod::system::set_fps(FPS_LIMIT);
+
+    // Prepare Tcod parameters
+    let root = Root::initializer()
+        .font("arial10x10.png", FontLayout::Tcod)
+        .font_type(FontType::Greyscale)
+        .size(SCREEN_WIDTH, SCREEN_HEIGHT)
+        .title("Rusty Roguelike")
+        .init();
+    let console = Offscreen::new(MAP_WIDTH, MAP_HEIGHT);
+    let panel = Offscreen::new(SCREEN_WIDTH, PANEL_HEIGHT);
+
+    // create Tcod
+    let mut tcod = Tcod {
+        root,
+        console,
+        panel,
+        key: Default::default(),
+        mouse: Default::default(),
+        keybindings,
+    };
+
+    main_menu(&mut tcod, &monster_templates, &options);
+}